@@ -1,19 +1,34 @@
-use std::{borrow::Cow, env};
+use std::{borrow::Cow, collections::HashMap, env};
 
 use anyhow::Context as _;
 use chrono::{DateTime, Utc};
+use futures::future;
 use llm::{
+    LLMProvider,
     builder::{LLMBackend, LLMBuilder},
     chat::{ChatMessage, Usage},
 };
 use rand::seq::SliceRandom;
 use serde::{Deserialize, Serialize};
 use serenity::all::{
-    CacheHttp, CommandInteraction, Context, CreateAttachment, CreateInteractionResponseFollowup,
-    Message, MessageId, UserId,
+    Builder, Cache, CacheHttp, CommandInteraction, Context, CreateAllowedMentions,
+    CreateAttachment, CreateInteractionResponseFollowup, CreateMessage, GuildId, Message,
+    MessageId, ModalInteraction, User, UserId,
 };
+use tracing::{info, warn};
 
-use crate::{context::MakaiContextChannel, utils::user_to_name};
+use crate::{
+    context::MakaiContextChannel,
+    mentions::{MentionPolicy, MentionSource, restore_mentions, sanitize_mass_mentions},
+    moderation::{ModerationStage, Moderator, Verdict},
+    scoring::CandidateScorer,
+    settings::GenerationSettings,
+    utils::user_to_name,
+    words::same_word,
+};
+
+/// Inputs longer than this always get a single candidate
+const BEST_OF_MAX_INPUT_LEN: usize = 280;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MakaiMessage {
@@ -21,199 +36,568 @@ pub struct MakaiMessage {
     pub timestamp: DateTime<Utc>,
     pub sender: MessageSender,
     pub content: String,
+    /// Shared by a prompt and the reply it produced, so they can be undone together
+    #[serde(default)]
+    pub exchange: Option<u64>,
+    #[serde(default)]
+    pub kind: MessageKind,
+    /// Copied from the parent's memory when an inheriting thread was created. It only gives
+    /// the prompt context, undo, forget, export and retention leave it alone
+    #[serde(default)]
+    pub inherited: bool,
+}
+
+/// Whether a memory entry is a message, or a lightweight event about another message
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MessageKind {
+    #[default]
+    Message,
+    /// Makai reacted to the message with `message_id`, the content holds the emoji
+    Reaction,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MessageSender {
     MakaiBot,
-    User(String),
+    User(UserSender),
+}
+
+/// A user who sent a message, with the names they had when it was remembered. Prompts use the
+/// names they go by now, see `NameResolver`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserSender {
+    /// Unset for messages remembered before ids were stored alongside them
+    pub id: Option<UserId>,
+    /// Their global display name, or username if they have none
+    pub name: String,
+    /// Their nickname in the guild the message was sent in
+    #[serde(default)]
+    pub nickname: Option<String>,
+}
+
+impl MessageSender {
+    pub fn user(user: &User, nickname: Option<String>) -> Self {
+        MessageSender::User(UserSender {
+            id: Some(user.id),
+            name: user_to_name(user).to_string(),
+            nickname,
+        })
+    }
+}
+
+impl UserSender {
+    /// The name they went by when the message was remembered
+    pub fn snapshot_name(&self) -> &str {
+        self.nickname.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// Looks up the names users go by now through the cache, so renames don't split one person
+/// into several in the history
+#[derive(Clone, Copy)]
+pub struct NameResolver<'a> {
+    pub cache: &'a Cache,
+    pub guild_id: Option<GuildId>,
+}
+
+impl NameResolver<'_> {
+    /// The sender's current nickname or display name, or the one remembered with the message
+    /// if they aren't cached
+    pub fn name(&self, sender: &UserSender) -> String {
+        let Some(id) = sender.id else {
+            return sender.snapshot_name().to_string();
+        };
+
+        self.guild_id
+            .and_then(|guild| self.cache.guild(guild))
+            .and_then(|guild| {
+                guild
+                    .members
+                    .get(&id)
+                    .map(|it| it.display_name().to_string())
+            })
+            .or_else(|| self.cache.user(id).map(|it| user_to_name(&it).to_string()))
+            .unwrap_or_else(|| sender.snapshot_name().to_string())
+    }
+
+    /// How a message's sender is shown, makai included
+    pub fn sender_name(&self, sender: &MessageSender) -> String {
+        match sender {
+            MessageSender::MakaiBot => "MakAI".to_string(),
+            MessageSender::User(sender) => self.name(sender),
+        }
+    }
 }
 
 impl MakaiMessage {
-    pub fn from_message_command(bot_id: UserId, interaction: &CommandInteraction) -> Option<Self> {
-        if let Some(Message {
+    pub fn from_message_command(
+        cache: &Cache,
+        bot_id: UserId,
+        interaction: &CommandInteraction,
+    ) -> Option<Self> {
+        let message = interaction.data.resolved.messages.values().next()?;
+
+        Some(Self::from_message(
+            cache,
+            bot_id,
+            interaction.guild_id,
+            message,
+        ))
+    }
+
+    pub fn from_message(
+        cache: &Cache,
+        bot_id: UserId,
+        guild_id: Option<GuildId>,
+        message: &Message,
+    ) -> Self {
+        let Message {
             id,
             author,
             timestamp,
             content,
             embeds,
+            mentions,
+            mention_channels,
             ..
-        }) = interaction.data.resolved.messages.values().next()
-        {
-            let timestamp = timestamp.to_utc();
+        } = message;
 
-            let sender = if bot_id == author.id {
-                MessageSender::MakaiBot
-            } else {
-                MessageSender::User(user_to_name(author).to_string())
-            };
+        let timestamp = timestamp.to_utc();
 
-            let mut content = content.clone();
-
-            for embed in embeds {
-                content.push_str(&format!(
-                    "\nThe user's message included a link: Title: `{}`, Description: `{}`",
-                    embed
-                        .title
-                        .as_ref()
-                        .map(|it| it.as_str())
-                        .unwrap_or("Unknown"),
-                    embed
-                        .description
-                        .as_ref()
-                        .map(|it| it.as_str())
-                        .unwrap_or("Unknown")
-                ));
-            }
-
-            Some(Self {
-                message_id: Some(*id),
-                timestamp,
-                sender,
-                content,
-            })
+        let sender = if bot_id == author.id {
+            MessageSender::MakaiBot
         } else {
-            None
+            let nickname = message.member.as_ref().and_then(|it| it.nick.clone());
+            MessageSender::user(author, nickname)
+        };
+
+        let mut content = MentionSource {
+            cache,
+            guild_id,
+            users: mentions,
+            channels: mention_channels,
+        }
+        .resolve(content);
+
+        for embed in embeds {
+            content.push_str(&format!(
+                "\nThe user's message included a link: Title: `{}`, Description: `{}`",
+                embed.title.as_deref().unwrap_or("Unknown"),
+                embed.description.as_deref().unwrap_or("Unknown")
+            ));
+        }
+
+        Self {
+            message_id: Some(*id),
+            timestamp,
+            sender,
+            content,
+            exchange: None,
+            kind: MessageKind::Message,
+            inherited: false,
         }
     }
 
-    pub fn from_assistant_response(content: String) -> Self {
+    pub fn from_assistant_response(content: String, exchange: Option<u64>) -> Self {
         Self {
             message_id: None,
             timestamp: Utc::now(),
             sender: MessageSender::MakaiBot,
             content,
+            exchange,
+            kind: MessageKind::Message,
+            inherited: false,
+        }
+    }
+
+    pub fn from_reaction(target: MessageId, emoji: String) -> Self {
+        Self {
+            message_id: Some(target),
+            timestamp: Utc::now(),
+            sender: MessageSender::MakaiBot,
+            content: emoji,
+            exchange: None,
+            kind: MessageKind::Reaction,
+            inherited: false,
+        }
+    }
+
+    /// The id of the user who sent the message, none for makai's own
+    pub fn author_id(&self) -> Option<UserId> {
+        match &self.sender {
+            MessageSender::User(sender) => sender.id,
+            MessageSender::MakaiBot => None,
         }
     }
 
-    pub fn to_chat_message(&self) -> ChatMessage {
+    pub fn to_chat_message(&self, names: NameResolver) -> ChatMessage {
+        if self.kind == MessageKind::Reaction {
+            return ChatMessage::assistant()
+                .content(format!("You (MakAI) reacted with: {}", self.content))
+                .build();
+        }
+
         match &self.sender {
             MessageSender::MakaiBot => ChatMessage::assistant()
                 .content(format!("You (MakAI) said: {}", self.content))
                 .build(),
             MessageSender::User(sender) => ChatMessage::user()
-                .content(format!("User `{sender}` said: {}", self.content))
+                .content(format!(
+                    "User `{}` said: {}",
+                    names.name(sender),
+                    self.content
+                ))
                 .build(),
         }
     }
 }
 
-pub async fn run_llm(
-    ctx: &MakaiContextChannel,
-    message: MakaiMessage,
-) -> anyhow::Result<LlmResponse> {
-    let url = env::var("LLM_API").context("Expected a llm api url in env")?;
-    let api_key = env::var("LLM_API_KEY").unwrap_or_else(|_| "fake-api-key".to_string());
-    let model = env::var("LLM_MODEL").context("Expected a llm model in env")?;
-    let prompt_file = env::var("LLM_PROMPT_FILE").unwrap_or_else(|_| "./prompt.txt".to_string());
+/// The global word list from `LLM_WORDS_FILE`, followed by the guild's words not already in it
+pub async fn load_words(guild_words: &[String]) -> anyhow::Result<Vec<String>> {
     let words_file = env::var("LLM_WORDS_FILE").unwrap_or_else(|_| "./words.txt".to_string());
 
-    let system = tokio::fs::read_to_string(prompt_file)
-        .await
-        .context("Read prompt file")?;
     let words = tokio::fs::read_to_string(words_file)
         .await
         .context("Read words file")?;
-    let mut words = words.lines().collect::<Vec<&str>>();
+
+    let mut words = words.lines().map(str::to_string).collect::<Vec<_>>();
+    for word in guild_words {
+        if !words.iter().any(|it| same_word(it, word)) {
+            words.push(word.clone());
+        }
+    }
+
+    Ok(words)
+}
+
+/// Reads a system prompt, filling in `{WORDS}` with the shuffled makain word list
+pub async fn load_prompt(prompt_file: &str, guild_words: &[String]) -> anyhow::Result<String> {
+    let system = tokio::fs::read_to_string(prompt_file)
+        .await
+        .context("Read prompt file")?;
+    let mut words = load_words(guild_words).await?;
     words.shuffle(&mut rand::rng());
-    let system = system.replace(
+
+    Ok(system.replace(
         "{WORDS}",
         &words.iter().fold(String::new(), |mut acc, it| {
             acc.push_str(&format!("- {it}\n"));
             acc
         }),
-    );
+    ))
+}
 
-    let llm = LLMBuilder::new()
+pub fn build_llm(
+    settings: &GenerationSettings,
+    system: String,
+) -> anyhow::Result<Box<dyn LLMProvider>> {
+    llm_builder(settings, system)?
+        .build()
+        .context("Failed to build LLM")
+}
+
+/// An llm configured from `settings`, for callers that need extra options before building
+pub fn llm_builder(settings: &GenerationSettings, system: String) -> anyhow::Result<LLMBuilder> {
+    let url = env::var("LLM_API").context("Expected a llm api url in env")?;
+    let api_key = env::var("LLM_API_KEY").unwrap_or_else(|_| "fake-api-key".to_string());
+
+    let mut llm = LLMBuilder::new()
         .backend(LLMBackend::OpenAI)
         .api_key(api_key)
         .base_url(url)
-        .model(model)
-        .system(system)
-        .build()
-        .context("Failed to build LLM")?;
+        .model(&settings.model)
+        .system(system);
+    if let Some(temperature) = settings.temperature {
+        llm = llm.temperature(temperature);
+    }
+    if let Some(max_tokens) = settings.max_tokens {
+        llm = llm.max_tokens(max_tokens);
+    }
+
+    Ok(llm)
+}
+
+pub async fn run_llm(
+    ctx: &MakaiContextChannel,
+    settings: &GenerationSettings,
+    moderator: &Moderator,
+    names: NameResolver<'_>,
+    message: MakaiMessage,
+) -> anyhow::Result<LlmResponse> {
+    let system = load_prompt(&settings.prompt_file(), &settings.words).await?;
+    let llm = build_llm(settings, system)?;
+
+    let author = match (&message.sender, message.author_id()) {
+        (MessageSender::User(sender), Some(id)) => Some((id, names.name(sender))),
+        _ => None,
+    };
+    // Only who the model can see in the history, anyone older can't be part of the reply
+    let mut participants = ctx
+        .recent_participants(settings.history_length, names)
+        .await;
+    participants.extend(author);
+
+    if let Verdict::Blocked { .. } = moderator
+        .check(ModerationStage::Input, &message.content)
+        .await
+    {
+        // Blocked inputs never make it into memory
+        return Ok(LlmResponse {
+            response: moderator.refusal(),
+            usage: None,
+            participants,
+            ephemeral: false,
+            exchange: None,
+            refused: true,
+        });
+    }
+
+    let mut messages = ctx.chat_messages(settings.history_length, names).await;
+    messages.push(message.to_chat_message(names));
+
+    if let Some(facts) = settings.facts.render(&participants) {
+        messages.push(ChatMessage::user().content(facts).build());
+    }
 
-    let mut messages = ctx.chat_messages(20).await;
-    messages.push(message.to_chat_message());
     messages.push(
         ChatMessage::user()
             .content("Generate a makian reply to the previous message.")
             .build(),
     );
 
-    let response = llm.chat(&messages).await.context("LLM Error")?;
-    let text = response.text().unwrap_or_default();
+    // Only short banter is worth sampling several times, long form writing is too costly
+    let candidates = if message.content.chars().count() <= BEST_OF_MAX_INPUT_LEN {
+        settings.candidate_count()
+    } else {
+        1
+    };
+    let scorer_words = if candidates > 1 {
+        load_words(&settings.words).await?
+    } else {
+        Vec::new()
+    };
+    let recent_replies = ctx.recent_replies(10).await;
+    let scorer = CandidateScorer {
+        words: &scorer_words,
+        recent_replies: &recent_replies,
+    };
+
+    let mut attempt = 0;
+    let mut usage = None;
+    let text = loop {
+        let responses = future::join_all((0..candidates).map(|_| llm.chat(&messages))).await;
+
+        let mut allowed = Vec::new();
+        let mut error = None;
+        let mut failed = 0;
+        for response in responses {
+            // One failed candidate shouldn't cost the reply, the others are just as good
+            let response = match response {
+                Ok(response) => response,
+                Err(err) => {
+                    warn!("Candidate failed: {err:?}");
+                    failed += 1;
+                    error = Some(err);
+                    continue;
+                }
+            };
+            usage = sum_usage(usage, response.usage());
+
+            let text = response.text().unwrap_or_default();
+            if moderator.check(ModerationStage::Output, &text).await == Verdict::Allowed {
+                allowed.push(text);
+            }
+        }
+        if failed == candidates
+            && let Some(err) = error
+        {
+            return Err(err).context("LLM Error");
+        }
+
+        let best = if candidates > 1 {
+            allowed
+                .into_iter()
+                .enumerate()
+                .map(|(idx, text)| {
+                    let score = scorer.score(&text);
+                    info!(candidate = idx, ?score, "Scored candidate: {text:?}");
+                    (text, score)
+                })
+                .max_by(|(_, a), (_, b)| a.total.total_cmp(&b.total))
+                .map(|(text, _)| text)
+        } else {
+            allowed.pop()
+        };
+
+        match best {
+            Some(text) => break text,
+            None if attempt < moderator.regenerate_attempts() => attempt += 1,
+            None => break moderator.replacement(),
+        }
+    };
+
+    if let Some(usage) = &usage {
+        ctx.record_usage(usage).await;
+    }
 
     // Update stored context
-    ctx.add_message(message).await;
-    ctx.add_message(MakaiMessage::from_assistant_response(text.clone()))
-        .await;
+    let exchange = rand::random();
+    ctx.add_message(MakaiMessage {
+        exchange: Some(exchange),
+        ..message
+    })
+    .await;
+    ctx.add_message_with_retention(
+        MakaiMessage::from_assistant_response(text.clone(), Some(exchange)),
+        &settings.retention,
+    )
+    .await;
 
     Ok(LlmResponse {
         response: text,
-        usage: response.usage(),
+        usage,
+        participants,
+        ephemeral: false,
+        exchange: Some(exchange),
+        refused: false,
     })
 }
 
+/// Runs a one off prompt without touching any channel's memory, used for utility commands
+/// that don't speak as makai. Moderated the same way as `run_llm`
+pub async fn run_oneshot(
+    settings: &GenerationSettings,
+    moderator: &Moderator,
+    system: String,
+    content: String,
+) -> anyhow::Result<LlmResponse> {
+    if let Verdict::Blocked { .. } = moderator.check(ModerationStage::Input, &content).await {
+        return Ok(LlmResponse {
+            response: moderator.refusal(),
+            usage: None,
+            participants: HashMap::new(),
+            ephemeral: false,
+            exchange: None,
+            refused: true,
+        });
+    }
+
+    let llm = build_llm(settings, system)?;
+    let messages = [ChatMessage::user().content(content).build()];
+
+    let mut attempt = 0;
+    let mut usage = None;
+    let text = loop {
+        let response = llm.chat(&messages).await.context("LLM Error")?;
+        usage = sum_usage(usage, response.usage());
+
+        let text = response.text().unwrap_or_default();
+        if moderator.check(ModerationStage::Output, &text).await == Verdict::Allowed {
+            break text;
+        }
+
+        if attempt < moderator.regenerate_attempts() {
+            attempt += 1;
+        } else {
+            break moderator.replacement();
+        }
+    };
+
+    Ok(LlmResponse {
+        response: text,
+        usage,
+        participants: HashMap::new(),
+        ephemeral: false,
+        exchange: None,
+        refused: false,
+    })
+}
+
+/// The interaction a response gets sent as a follow up to
+pub struct FollowUpTarget<'a> {
+    pub user: UserId,
+    pub token: &'a str,
+}
+
+impl<'a> From<&'a CommandInteraction> for FollowUpTarget<'a> {
+    fn from(value: &'a CommandInteraction) -> Self {
+        Self {
+            user: value.user.id,
+            token: &value.token,
+        }
+    }
+}
+
+impl<'a> From<&'a ModalInteraction> for FollowUpTarget<'a> {
+    fn from(value: &'a ModalInteraction) -> Self {
+        Self {
+            user: value.user.id,
+            token: &value.token,
+        }
+    }
+}
+
+fn sum_usage(acc: Option<Usage>, usage: Option<Usage>) -> Option<Usage> {
+    match (acc, usage) {
+        (Some(acc), Some(usage)) => Some(Usage {
+            prompt_tokens: acc.prompt_tokens + usage.prompt_tokens,
+            completion_tokens: acc.completion_tokens + usage.completion_tokens,
+            total_tokens: acc.total_tokens + usage.total_tokens,
+            ..usage
+        }),
+        (acc, usage) => acc.or(usage),
+    }
+}
+
 pub struct LlmResponse {
     pub response: String,
     pub usage: Option<Usage>,
+    /// Users from the conversation that the response is allowed to mention by name
+    pub participants: HashMap<UserId, String>,
+    /// Only show the response to the user who ran the command
+    pub ephemeral: bool,
+    /// The exchange the response was stored under, if it was stored
+    pub exchange: Option<u64>,
+    /// Moderation blocked the input, so the response is the refusal
+    pub refused: bool,
 }
 
 impl LlmResponse {
+    pub fn ephemeral(self) -> Self {
+        Self {
+            ephemeral: true,
+            ..self
+        }
+    }
+
     pub async fn send_follow_up(
         &self,
         discord_ctx: Context,
-        cmd: &CommandInteraction,
-    ) -> anyhow::Result<()> {
-        let content = if let Some(usage) = &self.usage {
-            &format!(
-                "{}\n-# Generated {} tokens",
-                self.response.trim(),
-                usage.completion_tokens
-            )
-        } else {
-            &self.response
-        };
-        let follow_up = CreateInteractionResponseFollowup::default().content(content);
-        let res1 = cmd
-            .create_followup(discord_ctx.http(), follow_up)
+        target: impl Into<FollowUpTarget<'_>>,
+        mention_policy: MentionPolicy,
+    ) -> anyhow::Result<Message> {
+        let target = target.into();
+        let rendered = self.render(target.user, mention_policy);
+
+        let follow_up = CreateInteractionResponseFollowup::default()
+            .content(&rendered.content)
+            .ephemeral(self.ephemeral)
+            .allowed_mentions(rendered.allowed_mentions.clone());
+        let res1 = follow_up
+            .execute(discord_ctx.http(), (None, target.token))
             .await
             .context("Cannot followup command");
 
-        if let Err(_) = res1 {
-            let word_wrapped = self
-                .response
-                .lines()
-                .map(|it| {
-                    if it.len() > 100 {
-                        let mut cumlative_buf = String::new();
-                        let mut line_buf = String::new();
-
-                        for word in it.split_whitespace() {
-                            line_buf.push_str(word);
-                            if line_buf.len() > 70 {
-                                line_buf.push('\n');
-                                cumlative_buf.push_str(&line_buf);
-                                line_buf.clear();
-                            } else {
-                                line_buf.push(' ');
-                            }
-                        }
-                        cumlative_buf.push_str(&line_buf);
-                        Cow::Owned(cumlative_buf)
-                    } else {
-                        Cow::Borrowed(it)
-                    }
-                })
-                .fold(String::new(), |mut acc, chunk| {
-                    acc.push_str(chunk.trim());
-                    acc.push('\n');
-                    acc
-                });
-
+        let sent = if let Ok(sent) = res1 {
+            sent
+        } else {
             let follow_up = CreateInteractionResponseFollowup::default()
-                .add_file(CreateAttachment::bytes(word_wrapped.as_bytes(), "raw.txt"));
+                .add_file(CreateAttachment::bytes(
+                    rendered.word_wrapped().as_bytes(),
+                    "raw.txt",
+                ))
+                .ephemeral(self.ephemeral)
+                .allowed_mentions(rendered.allowed_mentions);
 
             let follow_up = if let Some(usage) = &self.usage {
                 follow_up.content(format!("-# Generated {} tokens", usage.completion_tokens))
@@ -221,11 +605,128 @@ impl LlmResponse {
                 follow_up
             };
 
-            cmd.create_followup(discord_ctx.http(), follow_up)
+            follow_up
+                .execute(discord_ctx.http(), (None, target.token))
+                .await
+                .context("Cannot followup command")?
+        };
+
+        Ok(sent)
+    }
+
+    /// Sends the response as a regular message in `message`'s channel, as a reply to it if
+    /// `reply` is set
+    pub async fn send_message(
+        &self,
+        discord_ctx: &Context,
+        message: &Message,
+        reply: bool,
+        mention_policy: MentionPolicy,
+    ) -> anyhow::Result<Message> {
+        let rendered = self.render(message.author.id, mention_policy);
+        let reference = |it: CreateMessage| {
+            if reply {
+                it.reference_message(message)
+            } else {
+                it
+            }
+        };
+
+        let create = CreateMessage::new()
+            .content(&rendered.content)
+            .allowed_mentions(rendered.allowed_mentions.clone());
+        let res1 = message
+            .channel_id
+            .send_message(discord_ctx.http(), reference(create))
+            .await
+            .context("Cannot send message");
+
+        let sent = if let Ok(sent) = res1 {
+            sent
+        } else {
+            let create = CreateMessage::new()
+                .add_file(CreateAttachment::bytes(
+                    rendered.word_wrapped().as_bytes(),
+                    "raw.txt",
+                ))
+                .allowed_mentions(rendered.allowed_mentions);
+
+            let create = if let Some(usage) = &self.usage {
+                create.content(format!("-# Generated {} tokens", usage.completion_tokens))
+            } else {
+                create
+            };
+
+            message
+                .channel_id
+                .send_message(discord_ctx.http(), reference(create))
                 .await
-                .context("Cannot followup command")?;
+                .context("Cannot send message")?
+        };
+
+        Ok(sent)
+    }
+
+    fn render(&self, invoker: UserId, mention_policy: MentionPolicy) -> RenderedResponse {
+        let response = sanitize_mass_mentions(&self.response);
+        let response = restore_mentions(&response, &self.participants);
+        let allowed_mentions = mention_policy.allowed_mentions(invoker, &self.participants);
+
+        let content = if let Some(usage) = &self.usage {
+            format!(
+                "{}\n-# Generated {} tokens",
+                response.trim(),
+                usage.completion_tokens
+            )
+        } else {
+            response.clone()
+        };
+
+        RenderedResponse {
+            response,
+            content,
+            allowed_mentions,
         }
+    }
+}
+
+struct RenderedResponse {
+    response: String,
+    content: String,
+    allowed_mentions: CreateAllowedMentions,
+}
+
+impl RenderedResponse {
+    /// The response wrapped to a readable width, for when it's too long to send as a message
+    /// and has to be sent as a file
+    fn word_wrapped(&self) -> String {
+        self.response
+            .lines()
+            .map(|it| {
+                if it.len() > 100 {
+                    let mut cumlative_buf = String::new();
+                    let mut line_buf = String::new();
 
-        Ok(())
+                    for word in it.split_whitespace() {
+                        line_buf.push_str(word);
+                        if line_buf.len() > 70 {
+                            line_buf.push('\n');
+                            cumlative_buf.push_str(&line_buf);
+                            line_buf.clear();
+                        } else {
+                            line_buf.push(' ');
+                        }
+                    }
+                    cumlative_buf.push_str(&line_buf);
+                    Cow::Owned(cumlative_buf)
+                } else {
+                    Cow::Borrowed(it)
+                }
+            })
+            .fold(String::new(), |mut acc, chunk| {
+                acc.push_str(chunk.trim());
+                acc.push('\n');
+                acc
+            })
     }
 }
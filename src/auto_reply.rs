@@ -0,0 +1,82 @@
+use anyhow::Context as _;
+use serenity::all::{Context, Message};
+use tracing::debug;
+
+use crate::ai::{self, MakaiMessage, NameResolver};
+use crate::context::MakaiContext;
+use crate::moderation::Moderator;
+use crate::settings::ChannelSettings;
+
+/// Replies to messages that mention makai or reply to one of its messages. Returns whether
+/// the message was handled, in which case it's already been stored in memory
+pub async fn handle_message(
+    bot_ctx: &MakaiContext,
+    discord_ctx: &Context,
+    message: &Message,
+) -> anyhow::Result<bool> {
+    // Never answer bots, two of them could talk to each other forever
+    if message.author.bot {
+        return Ok(false);
+    }
+
+    let Some(bot_user) = bot_ctx.user().await else {
+        return Ok(false);
+    };
+
+    let replied_to_bot = message
+        .referenced_message
+        .as_ref()
+        .is_some_and(|it| it.author.id == bot_user.id);
+    if !message.mentions_user_id(bot_user.id) && !replied_to_bot {
+        return Ok(false);
+    }
+
+    let auto_reply = bot_ctx
+        .channel_settings(message.guild_id, &message.channel_id)
+        .await
+        .or(&ChannelSettings::from_env()?)
+        .auto_reply
+        .unwrap_or_default();
+    // Opted out users are never answered, answering would store their message
+    if !auto_reply || bot_ctx.is_opted_out(&message.author.id).await {
+        return Ok(false);
+    }
+
+    debug!(
+        "Auto replying to message {} in {}",
+        message.id, message.channel_id
+    );
+
+    // Stops typing once dropped
+    let _typing = message.channel_id.start_typing(&discord_ctx.http);
+
+    let moderator = Moderator::for_channel(discord_ctx, message.guild_id, message.channel_id)
+        .await
+        .context("Load moderator")?;
+    let settings = bot_ctx
+        .generation_settings(message.guild_id, &message.channel_id)
+        .await
+        .context("Resolve settings")?;
+    let channel = bot_ctx.memory(message.guild_id, &message.channel_id).await;
+
+    let makai_message =
+        MakaiMessage::from_message(&discord_ctx.cache, bot_user.id, message.guild_id, message);
+    let names = NameResolver {
+        cache: &discord_ctx.cache,
+        guild_id: message.guild_id,
+    };
+    let response = ai::run_llm(&channel, &settings, &moderator, names, makai_message)
+        .await
+        .context("Run LLM")?;
+
+    let guild_settings = bot_ctx.guild_settings(message.guild_id).await;
+    let sent = response
+        .send_message(discord_ctx, message, true, guild_settings.mention_policy)
+        .await
+        .context("Send reply")?;
+    if let Some(exchange) = response.exchange {
+        channel.set_reply_message_id(exchange, sent.id).await;
+    }
+
+    Ok(true)
+}
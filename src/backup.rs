@@ -0,0 +1,124 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use chrono::Utc;
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+use crate::context::serde::MakaiContextSerde;
+use crate::schema;
+use crate::store::Store;
+
+const BACKUP_PREFIX: &str = "makai_state-";
+const BACKUP_SUFFIX: &str = ".json";
+
+fn backup_dir() -> PathBuf {
+    env::var("MAKAI_BACKUP_DIR")
+        .unwrap_or_else(|_| "./backups".to_string())
+        .into()
+}
+
+fn backup_count() -> usize {
+    env::var("MAKAI_BACKUP_COUNT")
+        .ok()
+        .and_then(|it| it.parse().ok())
+        .unwrap_or(5)
+}
+
+/// Writes to a temp file next to `path`, syncs it and renames it over `path`, so a crash
+/// leaves either the old file or the new one but never half of one
+pub async fn write_atomic(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    let mut file = tokio::fs::File::create(&tmp)
+        .await
+        .context("Create temp file")?;
+    file.write_all(data).await.context("Write temp file")?;
+    file.sync_all().await.context("Sync temp file")?;
+    drop(file);
+
+    tokio::fs::rename(&tmp, path)
+        .await
+        .context("Rename temp file")?;
+
+    // Make the rename itself durable
+    if let Some(dir) = path.parent().filter(|it| !it.as_os_str().is_empty()) {
+        tokio::fs::File::open(dir)
+            .await
+            .context("Open parent dir")?
+            .sync_all()
+            .await
+            .context("Sync parent dir")?;
+    }
+
+    Ok(())
+}
+
+/// Snapshots the store into a new timestamped backup, then drops all but the newest ones
+pub async fn write_backup(store: &Store) -> anyhow::Result<()> {
+    let state = tokio::task::block_in_place(|| store.load()).context("Read database")?;
+    let data = schema::encode_state(&state).context("Encode backup")?;
+
+    let dir = backup_dir();
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .context("Create backup dir")?;
+    let path = dir.join(format!(
+        "{BACKUP_PREFIX}{}{BACKUP_SUFFIX}",
+        Utc::now().format("%Y%m%dT%H%M%SZ")
+    ));
+    write_atomic(&path, &data).await.context("Write backup")?;
+    info!("Backed up state to {}", path.display());
+
+    for old in backups().await?.into_iter().skip(backup_count()) {
+        tokio::fs::remove_file(&old)
+            .await
+            .with_context(|| format!("Remove old backup {}", old.display()))?;
+    }
+
+    Ok(())
+}
+
+/// The newest backup that can still be parsed, skipping over broken ones
+pub async fn newest_valid_backup() -> anyhow::Result<Option<(PathBuf, MakaiContextSerde)>> {
+    for path in backups().await? {
+        let parsed = tokio::fs::read(&path)
+            .await
+            .context("Read backup")
+            .and_then(|it| schema::decode_state(&it).context("Parse backup"));
+
+        match parsed {
+            Ok(state) => return Ok(Some((path, state))),
+            Err(err) => warn!("Skipping broken backup {}: {err:#}", path.display()),
+        }
+    }
+
+    Ok(None)
+}
+
+/// Backup files, newest first
+async fn backups() -> anyhow::Result<Vec<PathBuf>> {
+    let dir = backup_dir();
+    if !tokio::fs::try_exists(&dir).await.unwrap_or(false) {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = Vec::new();
+    let mut entries = tokio::fs::read_dir(&dir).await.context("Read backup dir")?;
+    while let Some(entry) = entries.next_entry().await.context("Read backup dir")? {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with(BACKUP_PREFIX) && name.ends_with(BACKUP_SUFFIX) {
+            backups.push(entry.path());
+        }
+    }
+
+    // Timestamps are zero padded, so names sort by age
+    backups.sort();
+    backups.reverse();
+
+    Ok(backups)
+}
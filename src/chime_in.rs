@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+
+use anyhow::Context as _;
+use chrono::{DateTime, Timelike, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serenity::all::{ChannelId, Context, Message};
+use tracing::debug;
+
+use crate::ai::{self, MakaiMessage, NameResolver};
+use crate::context::MakaiContext;
+use crate::moderation::Moderator;
+use crate::passive;
+use crate::settings::ChannelSettings;
+
+/// How much more likely a chime in is when a message contains one of the channel's keywords
+const KEYWORD_BOOST: f32 = 3.0;
+/// How much more likely a chime in is when a message uses the makain word list
+const WORD_LIST_BOOST: f32 = 2.0;
+
+/// Decides when makai joins a conversation uninvited
+pub struct ChimeInScheduler {
+    rng: Mutex<StdRng>,
+    last_chime: Mutex<HashMap<ChannelId, DateTime<Utc>>>,
+}
+
+/// What a chime in decision was based on
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChimeInInputs {
+    pub chance: f32,
+    pub keyword_match: bool,
+    pub word_list_match: bool,
+    /// Seconds left until the channel's cooldown runs out
+    pub cooldown_left: i64,
+    pub quiet_hours: bool,
+}
+
+impl ChimeInInputs {
+    /// The chance of chiming in after boosts, zero while quiet or cooling down
+    pub fn probability(&self) -> f32 {
+        if self.quiet_hours || self.cooldown_left > 0 {
+            return 0.0;
+        }
+
+        let mut probability = self.chance;
+        if self.keyword_match {
+            probability *= KEYWORD_BOOST;
+        }
+        if self.word_list_match {
+            probability *= WORD_LIST_BOOST;
+        }
+
+        probability.clamp(0.0, 1.0)
+    }
+}
+
+impl ChimeInScheduler {
+    /// Seeds from `LLM_CHIME_IN_SEED` if set, otherwise from the os
+    pub fn from_env() -> Self {
+        match env::var("LLM_CHIME_IN_SEED")
+            .ok()
+            .and_then(|it| it.parse().ok())
+        {
+            Some(seed) => Self::with_seed(seed),
+            None => Self::with_rng(StdRng::from_os_rng()),
+        }
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(rng: StdRng) -> Self {
+        Self {
+            rng: Mutex::new(rng),
+            last_chime: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Gathers what a decision for `content` in `channel` depends on, `settings` should already
+    /// include the defaults from the env
+    pub fn inputs(
+        &self,
+        channel: ChannelId,
+        settings: &ChannelSettings,
+        words: &[String],
+        content: &str,
+        now: DateTime<Utc>,
+    ) -> ChimeInInputs {
+        let content = content.to_lowercase();
+
+        let keyword_match = settings
+            .chime_in_keywords
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(|it| it.trim().to_lowercase())
+            .filter(|it| !it.is_empty())
+            .any(|it| content.contains(&it));
+        let word_list_match = words
+            .iter()
+            .map(|it| it.trim().to_lowercase())
+            .filter(|it| it.len() > 2)
+            .any(|it| content.contains(&it));
+
+        let cooldown = settings.chime_in_cooldown.unwrap_or_default() as i64;
+        let cooldown_left = self
+            .last_chime
+            .lock()
+            .expect("Lock last chime")
+            .get(&channel)
+            .map(|last| cooldown - (now - *last).num_seconds())
+            .unwrap_or(0)
+            .max(0);
+
+        ChimeInInputs {
+            chance: settings.chime_in_chance.unwrap_or(0.0),
+            keyword_match,
+            word_list_match,
+            cooldown_left,
+            quiet_hours: settings
+                .quiet_hours
+                .is_some_and(|it| it.contains(now.hour())),
+        }
+    }
+
+    /// Rolls whether to chime in, starting the channel's cooldown if so
+    pub fn decide(&self, channel: ChannelId, inputs: &ChimeInInputs, now: DateTime<Utc>) -> bool {
+        let probability = inputs.probability();
+        let roll = self.rng.lock().expect("Lock rng").random::<f32>();
+        let chime_in = roll < probability;
+
+        debug!(
+            target: "makai::chime_in",
+            %channel, ?inputs, probability, roll, chime_in,
+            "Chime in decision"
+        );
+
+        if chime_in {
+            self.last_chime
+                .lock()
+                .expect("Lock last chime")
+                .insert(channel, now);
+        }
+
+        chime_in
+    }
+}
+
+/// Maybe replies to a message in a passive channel without being asked. Returns whether
+/// makai chimed in, in which case the message is already stored in memory
+pub async fn handle_message(
+    scheduler: &ChimeInScheduler,
+    bot_ctx: &MakaiContext,
+    discord_ctx: &Context,
+    message: &Message,
+) -> anyhow::Result<bool> {
+    if !passive::is_listening(bot_ctx, message).await {
+        return Ok(false);
+    }
+
+    let Some(bot_user) = bot_ctx.user().await else {
+        return Ok(false);
+    };
+
+    let settings = bot_ctx
+        .channel_settings(message.guild_id, &message.channel_id)
+        .await
+        .or(&ChannelSettings::from_env()?);
+    if settings.chime_in_chance.unwrap_or(0.0) <= 0.0 {
+        return Ok(false);
+    }
+
+    let guild_words = bot_ctx.guild_words(message.guild_id).await;
+    let words = ai::load_words(&guild_words).await.unwrap_or_default();
+    let now = Utc::now();
+    let inputs = scheduler.inputs(message.channel_id, &settings, &words, &message.content, now);
+    if !scheduler.decide(message.channel_id, &inputs, now) {
+        return Ok(false);
+    }
+
+    debug!(
+        "Chiming in after message {} in {}",
+        message.id, message.channel_id
+    );
+
+    let moderator = Moderator::for_channel(discord_ctx, message.guild_id, message.channel_id)
+        .await
+        .context("Load moderator")?;
+    let settings = bot_ctx
+        .generation_settings(message.guild_id, &message.channel_id)
+        .await
+        .context("Resolve settings")?;
+    let channel = bot_ctx.memory(message.guild_id, &message.channel_id).await;
+
+    let makai_message =
+        MakaiMessage::from_message(&discord_ctx.cache, bot_user.id, message.guild_id, message);
+    let names = NameResolver {
+        cache: &discord_ctx.cache,
+        guild_id: message.guild_id,
+    };
+    let response = ai::run_llm(&channel, &settings, &moderator, names, makai_message)
+        .await
+        .context("Run LLM")?;
+
+    let guild_settings = bot_ctx.guild_settings(message.guild_id).await;
+    let sent = response
+        .send_message(discord_ctx, message, false, guild_settings.mention_policy)
+        .await
+        .context("Send chime in")?;
+    if let Some(exchange) = response.exchange {
+        channel.set_reply_message_id(exchange, sent.id).await;
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::settings::QuietHours;
+
+    fn settings(chance: f32) -> ChannelSettings {
+        ChannelSettings {
+            chime_in_chance: Some(chance),
+            chime_in_cooldown: Some(60),
+            chime_in_keywords: Some("pizza, makai".to_string()),
+            quiet_hours: Some(QuietHours { start: 23, end: 7 }),
+            ..Default::default()
+        }
+    }
+
+    fn noon() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 1, 1, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn same_seed_same_decisions() {
+        let decisions = |seed| {
+            let scheduler = ChimeInScheduler::with_seed(seed);
+            (0..32)
+                .map(|it| {
+                    let channel = ChannelId::new(it + 1);
+                    let inputs = scheduler.inputs(channel, &settings(0.5), &[], "hi", noon());
+                    scheduler.decide(channel, &inputs, noon())
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(decisions(7), decisions(7));
+    }
+
+    #[test]
+    fn boosts_multiply_chance() {
+        let scheduler = ChimeInScheduler::with_seed(0);
+        let words = ["bruh".to_string()];
+        let inputs = scheduler.inputs(
+            ChannelId::new(1),
+            &settings(0.1),
+            &words,
+            "Pizza time bruh",
+            noon(),
+        );
+
+        assert!(inputs.keyword_match && inputs.word_list_match);
+        assert!((inputs.probability() - 0.6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn quiet_hours_wrap_midnight() {
+        let scheduler = ChimeInScheduler::with_seed(0);
+        let late = Utc.with_ymd_and_hms(2026, 1, 1, 2, 0, 0).unwrap();
+        let inputs = scheduler.inputs(ChannelId::new(1), &settings(1.0), &[], "hi", late);
+
+        assert!(inputs.quiet_hours);
+        assert!(!scheduler.decide(ChannelId::new(1), &inputs, late));
+    }
+
+    #[test]
+    fn cooldown_blocks_until_elapsed() {
+        let scheduler = ChimeInScheduler::with_seed(0);
+        let channel = ChannelId::new(1);
+        let settings = settings(1.0);
+
+        let inputs = scheduler.inputs(channel, &settings, &[], "hi", noon());
+        assert!(scheduler.decide(channel, &inputs, noon()));
+
+        let soon = noon() + chrono::Duration::seconds(30);
+        let inputs = scheduler.inputs(channel, &settings, &[], "hi", soon);
+        assert_eq!(inputs.cooldown_left, 30);
+        assert!(!scheduler.decide(channel, &inputs, soon));
+
+        let later = noon() + chrono::Duration::seconds(61);
+        let inputs = scheduler.inputs(channel, &settings, &[], "hi", later);
+        assert!(scheduler.decide(channel, &inputs, later));
+    }
+}
@@ -4,24 +4,99 @@ use std::collections::HashMap;
 use tracing::error;
 
 use serenity::all::{
-    Command, CommandInteraction, Context, CreateCommand, CreateInteractionResponse,
-    CreateInteractionResponseFollowup, CreateInteractionResponseMessage, InteractionResponseFlags,
+    ChannelId, Command, CommandInteraction, ComponentInteraction, Context, CreateCommand,
+    CreateInteractionResponse, CreateInteractionResponseFollowup, CreateInteractionResponseMessage,
+    InteractionResponseFlags, ModalInteraction, Permissions,
 };
 
 use crate::{
     commands::{
-        chat::ChatCommand, remember::RememberCommand, reply::ReplyCommand, reset::ResetCommand,
+        chat::ChatCommand,
+        config::ConfigCommand,
+        fact::FactCommand,
+        forget::ForgetCommand,
+        makaify::{MakaifyCommand, MakaifyMessageCommand},
+        memory::MemoryCommand,
+        mentions::MentionsCommand,
+        persona::PersonaCommand,
+        privacy::PrivacyCommand,
+        react::ReactCommand,
+        remember::RememberCommand,
+        reply::ReplyCommand,
+        reset::ResetCommand,
+        translate::{TranslateCommand, TranslateMessageCommand},
+        undo::UndoCommand,
+        words::WordsCommand,
     },
-    context::MakaiContext,
+    context::{MakaiContext, SharedMemoryId},
+    store::MemoryId,
 };
 
 pub mod chat;
+pub mod config;
+pub mod fact;
+pub mod forget;
+pub mod makaify;
+pub mod memory;
+pub mod mentions;
+pub mod persona;
+pub mod privacy;
+pub mod react;
 pub mod remember;
 pub mod reply;
 pub mod reset;
+pub mod translate;
+pub mod undo;
+pub mod words;
 
 pub type CommandName = &'static str;
 
+/// Checks whether someone with `permissions` may clear, edit or read all of `memory`. Shared
+/// memories reach beyond the channel the command was used in, so a group's needs Manage
+/// Channels and the whole server's needs Manage Server. Returns the reason to show otherwise
+pub fn check_memory_access(
+    memory: &MemoryId,
+    permissions: Option<Permissions>,
+) -> Result<(), &'static str> {
+    match memory {
+        MemoryId::Channel(_) => Ok(()),
+        MemoryId::Shared(SharedMemoryId { group: None, .. }) => {
+            if permissions.is_some_and(|it| it.manage_guild()) {
+                Ok(())
+            } else {
+                Err(
+                    "This channel uses the server's shared memory, which needs the Manage Server \
+                     permission",
+                )
+            }
+        }
+        MemoryId::Shared(SharedMemoryId { group: Some(_), .. }) => {
+            if permissions.is_some_and(|it| it.manage_channels()) {
+                Ok(())
+            } else {
+                Err(
+                    "This channel uses a group's shared memory, which needs the Manage Channels \
+                     permission",
+                )
+            }
+        }
+    }
+}
+
+/// Names the memory `channel` uses, eg. "this channel's memory"
+pub fn describe_memory(memory: &MemoryId, channel: ChannelId) -> String {
+    match memory {
+        MemoryId::Channel(id) if *id == channel => "this channel's memory".to_string(),
+        MemoryId::Channel(id) => format!("the memory of <#{id}>"),
+        MemoryId::Shared(SharedMemoryId { group: None, .. }) => {
+            "the server's shared memory".to_string()
+        }
+        MemoryId::Shared(SharedMemoryId {
+            group: Some(group), ..
+        }) => format!("the memory of the `{group}` group"),
+    }
+}
+
 #[async_trait]
 pub trait MakaiCommand {
     fn name(&self) -> CommandName;
@@ -32,6 +107,37 @@ pub trait MakaiCommand {
         discord_ctx: Context,
         cmd: &CommandInteraction,
     ) -> anyhow::Result<()>;
+
+    /// Responds to autocomplete requests for this command's options
+    async fn autocomplete(
+        &self,
+        _bot_ctx: &MakaiContext,
+        _discord_ctx: Context,
+        _cmd: &CommandInteraction,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Handles components (eg. buttons) sent by this command, their custom ids are prefixed
+    /// by `<name>:`
+    async fn component(
+        &self,
+        _bot_ctx: &MakaiContext,
+        _discord_ctx: Context,
+        _component: &ComponentInteraction,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Handles modals opened by this command, their custom ids are prefixed by `<name>:`
+    async fn modal(
+        &self,
+        _bot_ctx: &MakaiContext,
+        _discord_ctx: Context,
+        _modal: &ModalInteraction,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
 }
 
 pub struct MakaiCommandRegistry<'a> {
@@ -79,7 +185,7 @@ impl<'a> MakaiCommandRegistry<'a> {
 
         let res = cmd.run(bot_ctx, discord_ctx.clone(), interaction).await;
 
-        if let Err(_) = res {
+        if res.is_err() {
             let follow_up = CreateInteractionResponseFollowup::default()
                 .content("An error occoured while processing your command!");
             interaction
@@ -90,6 +196,53 @@ impl<'a> MakaiCommandRegistry<'a> {
 
         res
     }
+
+    pub async fn handle_autocomplete(
+        &self,
+        bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        interaction: &CommandInteraction,
+    ) -> anyhow::Result<()> {
+        let Some(cmd) = self.commands.get(interaction.data.name.as_str()) else {
+            return Ok(());
+        };
+
+        cmd.autocomplete(bot_ctx, discord_ctx, interaction).await
+    }
+
+    pub async fn handle_component(
+        &self,
+        bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        interaction: &ComponentInteraction,
+    ) -> anyhow::Result<()> {
+        let Some(cmd) = self.commands.get(command_name(&interaction.data.custom_id)) else {
+            return Ok(());
+        };
+
+        cmd.component(bot_ctx, discord_ctx, interaction).await
+    }
+
+    pub async fn handle_modal(
+        &self,
+        bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        interaction: &ModalInteraction,
+    ) -> anyhow::Result<()> {
+        let Some(cmd) = self.commands.get(command_name(&interaction.data.custom_id)) else {
+            return Ok(());
+        };
+
+        cmd.modal(bot_ctx, discord_ctx, interaction).await
+    }
+}
+
+/// The command that owns a component or modal, from its `<name>:...` custom id
+fn command_name(custom_id: &str) -> &str {
+    custom_id
+        .split_once(':')
+        .map(|(name, _)| name)
+        .unwrap_or(custom_id)
 }
 
 impl Default for MakaiCommandRegistry<'_> {
@@ -100,6 +253,20 @@ impl Default for MakaiCommandRegistry<'_> {
         reg.add_command(ChatCommand);
         reg.add_command(RememberCommand);
         reg.add_command(ResetCommand);
+        reg.add_command(UndoCommand);
+        reg.add_command(ForgetCommand);
+        reg.add_command(MemoryCommand);
+        reg.add_command(PrivacyCommand);
+        reg.add_command(MentionsCommand);
+        reg.add_command(ConfigCommand);
+        reg.add_command(TranslateCommand);
+        reg.add_command(TranslateMessageCommand);
+        reg.add_command(MakaifyCommand);
+        reg.add_command(MakaifyMessageCommand);
+        reg.add_command(ReactCommand);
+        reg.add_command(WordsCommand);
+        reg.add_command(PersonaCommand);
+        reg.add_command(FactCommand);
 
         reg
     }
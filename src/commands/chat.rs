@@ -8,10 +8,11 @@ use serenity::all::{
 use serenity::builder::{CreateCommand, CreateCommandOption};
 use serenity::model::application::CommandOptionType;
 
-use crate::ai::{self, MakaiMessage, MessageSender};
+use crate::ai::{self, MakaiMessage, MessageKind, MessageSender, NameResolver};
 use crate::commands::{CommandName, MakaiCommand};
 use crate::context::MakaiContext;
-use crate::utils::user_to_name;
+use crate::mentions::MentionSource;
+use crate::moderation::Moderator;
 
 pub struct ChatCommand;
 
@@ -52,20 +53,49 @@ impl MakaiCommand for ChatCommand {
         else {
             bail!("Find prompy")
         };
+        let content = MentionSource {
+            cache: &discord_ctx.cache,
+            guild_id: cmd.guild_id,
+            users: &[],
+            channels: &[],
+        }
+        .resolve(prompt);
         let message = MakaiMessage {
             message_id: None,
             timestamp: Utc::now(),
-            sender: MessageSender::User(user_to_name(&cmd.user).to_string()),
-            content: prompt.to_string(),
+            sender: MessageSender::user(
+                &cmd.user,
+                cmd.member.as_ref().and_then(|it| it.nick.clone()),
+            ),
+            content,
+            exchange: None,
+            kind: MessageKind::Message,
+            inherited: false,
         };
 
-        let response = ai::run_llm(&*bot_ctx.channel(&cmd.channel_id).await, message)
+        let moderator = Moderator::for_channel(&discord_ctx, cmd.guild_id, cmd.channel_id)
+            .await
+            .context("Load moderator")?;
+        let settings = bot_ctx
+            .generation_settings(cmd.guild_id, &cmd.channel_id)
+            .await
+            .context("Resolve settings")?;
+        let channel = bot_ctx.memory(cmd.guild_id, &cmd.channel_id).await;
+        let names = NameResolver {
+            cache: &discord_ctx.cache,
+            guild_id: cmd.guild_id,
+        };
+        let response = ai::run_llm(&channel, &settings, &moderator, names, message)
             .await
             .context("Run LLM")?;
-        response
-            .send_follow_up(discord_ctx, cmd)
+        let guild_settings = bot_ctx.guild_settings(cmd.guild_id).await;
+        let sent = response
+            .send_follow_up(discord_ctx, cmd, guild_settings.mention_policy)
             .await
             .context("Send Follow up")?;
+        if let Some(exchange) = response.exchange {
+            channel.set_reply_message_id(exchange, sent.id).await;
+        }
 
         Ok(())
     }
@@ -0,0 +1,321 @@
+use anyhow::{Context as _, bail};
+use async_trait::async_trait;
+use serenity::all::{
+    CommandInteraction, Context, CreateAutocompleteResponse, CreateInteractionResponse,
+    CreateInteractionResponseMessage, InteractionContext, InteractionResponseFlags, Permissions,
+    ResolvedOption, ResolvedValue,
+};
+use serenity::builder::{CreateCommand, CreateCommandOption};
+use serenity::model::application::CommandOptionType;
+
+use crate::commands::{CommandName, MakaiCommand};
+use crate::context::MakaiContext;
+use crate::settings::{ChannelSettings, SettingName};
+
+pub struct ConfigCommand;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfigScope {
+    Channel,
+    Guild,
+}
+
+#[async_trait]
+impl MakaiCommand for ConfigCommand {
+    fn name(&self) -> CommandName {
+        "config"
+    }
+
+    fn register(&self) -> CreateCommand {
+        let setting = |required| {
+            CreateCommandOption::new(CommandOptionType::String, "setting", "The setting")
+                .required(required)
+                .set_autocomplete(true)
+        };
+        let scope = || {
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "scope",
+                "Whether to change this channel or the server wide defaults",
+            )
+            .add_string_choice("This channel", "channel")
+            .add_string_choice("Server defaults", "guild")
+        };
+
+        CreateCommand::new(self.name())
+            .add_context(InteractionContext::BotDm)
+            .add_context(InteractionContext::Guild)
+            .default_member_permissions(Permissions::MANAGE_CHANNELS)
+            .description("Configure how makai generates responses")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "get",
+                    "Show the current settings",
+                )
+                .add_sub_option(setting(false))
+                .add_sub_option(scope()),
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::SubCommand, "set", "Change a setting")
+                    .add_sub_option(setting(true))
+                    .add_sub_option(
+                        CreateCommandOption::new(
+                            CommandOptionType::String,
+                            "value",
+                            "The new value",
+                        )
+                        .required(true),
+                    )
+                    .add_sub_option(scope()),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "reset",
+                    "Reset a setting to its default",
+                )
+                .add_sub_option(setting(true))
+                .add_sub_option(scope()),
+            )
+    }
+
+    async fn run(
+        &self,
+        bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        cmd: &CommandInteraction,
+    ) -> anyhow::Result<()> {
+        let options = cmd.data.options();
+        let Some(ResolvedOption {
+            name: subcommand,
+            value: ResolvedValue::SubCommand(options),
+            ..
+        }) = options.first()
+        else {
+            bail!("Find subcommand")
+        };
+
+        let string_option = |name: &str| {
+            options.iter().find_map(|it| match it.value {
+                ResolvedValue::String(value) if it.name == name => Some(value),
+                _ => None,
+            })
+        };
+
+        let scope = match string_option("scope") {
+            Some("guild") => ConfigScope::Guild,
+            _ => ConfigScope::Channel,
+        };
+        let setting = string_option("setting");
+
+        let content = match self
+            .apply(
+                bot_ctx,
+                cmd,
+                subcommand,
+                scope,
+                setting,
+                string_option("value"),
+            )
+            .await
+        {
+            Ok(content) => content,
+            Err(err) => format!("{err:#}"),
+        };
+
+        let message = CreateInteractionResponseMessage::default()
+            .flags(InteractionResponseFlags::EPHEMERAL)
+            .content(content);
+        let response = CreateInteractionResponse::Message(message);
+        cmd.create_response(&discord_ctx.http, response)
+            .await
+            .context("Cannot ack command")?;
+
+        Ok(())
+    }
+
+    async fn autocomplete(
+        &self,
+        _bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        cmd: &CommandInteraction,
+    ) -> anyhow::Result<()> {
+        let Some(focused) = cmd.data.autocomplete() else {
+            return Ok(());
+        };
+
+        let query = focused.value.to_lowercase();
+        let choices = SettingName::ALL
+            .iter()
+            .filter(|it| it.id().contains(&query))
+            .fold(CreateAutocompleteResponse::new(), |acc, it| {
+                acc.add_string_choice(it.id(), it.id())
+            });
+
+        cmd.create_response(
+            &discord_ctx.http,
+            CreateInteractionResponse::Autocomplete(choices),
+        )
+        .await
+        .context("Respond to autocomplete")?;
+
+        Ok(())
+    }
+}
+
+impl ConfigCommand {
+    /// Applies the subcommand, returning the message to show the user. User facing problems
+    /// are returned as errors so they can be shown as is
+    async fn apply(
+        &self,
+        bot_ctx: &MakaiContext,
+        cmd: &CommandInteraction,
+        subcommand: &str,
+        scope: ConfigScope,
+        setting: Option<&str>,
+        value: Option<&str>,
+    ) -> anyhow::Result<String> {
+        let setting = setting
+            .map(|it| SettingName::from_id(it).with_context(|| format!("Unknown setting `{it}`")))
+            .transpose()?;
+
+        if scope == ConfigScope::Guild {
+            let can_manage_guild = cmd
+                .member
+                .as_ref()
+                .and_then(|it| it.permissions)
+                .is_some_and(|it| it.manage_guild());
+            if cmd.guild_id.is_none() {
+                bail!("Server defaults can only be changed in a server");
+            }
+            if subcommand != "get" && !can_manage_guild {
+                bail!("You need the Manage Server permission to change server defaults");
+            }
+        }
+
+        let current = self.settings(bot_ctx, cmd, scope).await;
+
+        match (subcommand, setting) {
+            ("get", Some(setting)) => Ok(format!(
+                "`{}` = {}",
+                setting.id(),
+                describe(&current, setting)
+            )),
+            ("get", None) => {
+                let effective = bot_ctx
+                    .generation_settings(cmd.guild_id, &cmd.channel_id)
+                    .await
+                    .ok();
+                let channel_settings = bot_ctx
+                    .channel_settings(cmd.guild_id, &cmd.channel_id)
+                    .await;
+
+                Ok(SettingName::ALL
+                    .iter()
+                    .map(|it| {
+                        let effective = effective.as_ref().and_then(|effective| match it {
+                            SettingName::Temperature => {
+                                effective.temperature.map(|it| it.to_string())
+                            }
+                            SettingName::HistoryLength => {
+                                Some(effective.history_length.to_string())
+                            }
+                            SettingName::MaxTokens => effective.max_tokens.map(|it| it.to_string()),
+                            SettingName::Model => Some(effective.model.clone()),
+                            SettingName::Persona => effective.persona.clone(),
+                            SettingName::Candidates => {
+                                Some(effective.candidate_count().to_string())
+                            }
+                            SettingName::Passive => {
+                                Some(channel_settings.passive.unwrap_or(false).to_string())
+                            }
+                            SettingName::AutoReply => {
+                                Some(channel_settings.auto_reply.unwrap_or(true).to_string())
+                            }
+                            SettingName::ChimeInChance
+                            | SettingName::ChimeInCooldown
+                            | SettingName::ChimeInKeywords
+                            | SettingName::QuietHours
+                            | SettingName::ReactChance
+                            | SettingName::RetentionCount
+                            | SettingName::RetentionDays
+                            | SettingName::InactiveDays
+                            | SettingName::ThreadMemory
+                            | SettingName::MemoryScope => channel_settings.get(*it),
+                        });
+
+                        format!(
+                            "`{}` = {} (effective: {})",
+                            it.id(),
+                            describe(&current, *it),
+                            effective.as_deref().unwrap_or("default")
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            }
+            ("set", Some(setting)) => {
+                let value = value.context("Missing value")?;
+                let mut updated = current;
+                updated.set(setting, value)?;
+                self.store(bot_ctx, cmd, scope, updated).await;
+
+                Ok(format!("Set `{}` to `{value}`", setting.id()))
+            }
+            ("reset", Some(setting)) => {
+                let mut updated = current;
+                updated.reset(setting);
+                self.store(bot_ctx, cmd, scope, updated).await;
+
+                Ok(format!("Reset `{}`", setting.id()))
+            }
+            _ => bail!("Unknown subcommand `{subcommand}`"),
+        }
+    }
+
+    async fn settings(
+        &self,
+        bot_ctx: &MakaiContext,
+        cmd: &CommandInteraction,
+        scope: ConfigScope,
+    ) -> ChannelSettings {
+        match scope {
+            ConfigScope::Channel => bot_ctx.channel(&cmd.channel_id).await.settings().await,
+            ConfigScope::Guild => bot_ctx.guild_settings(cmd.guild_id).await.channel_defaults,
+        }
+    }
+
+    async fn store(
+        &self,
+        bot_ctx: &MakaiContext,
+        cmd: &CommandInteraction,
+        scope: ConfigScope,
+        settings: ChannelSettings,
+    ) {
+        match (scope, cmd.guild_id) {
+            (ConfigScope::Channel, _) => {
+                bot_ctx
+                    .channel(&cmd.channel_id)
+                    .await
+                    .update_settings(|it| *it = settings)
+                    .await
+            }
+            (ConfigScope::Guild, Some(guild_id)) => {
+                bot_ctx
+                    .guild(&guild_id)
+                    .await
+                    .update_settings(|it| it.channel_defaults = settings)
+                    .await
+            }
+            (ConfigScope::Guild, None) => {}
+        }
+    }
+}
+
+fn describe(settings: &ChannelSettings, setting: SettingName) -> String {
+    settings
+        .get(setting)
+        .map(|it| format!("`{it}`"))
+        .unwrap_or_else(|| "unset".to_string())
+}
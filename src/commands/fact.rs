@@ -0,0 +1,353 @@
+use anyhow::{Context as _, bail};
+use async_trait::async_trait;
+use serenity::all::{
+    AutocompleteChoice, CommandDataOptionValue, CommandInteraction, Context,
+    CreateAutocompleteResponse, CreateInteractionResponse, CreateInteractionResponseFollowup,
+    CreateInteractionResponseMessage, InteractionContext, ResolvedOption, ResolvedValue, UserId,
+};
+use serenity::builder::{CreateCommand, CreateCommandOption};
+use serenity::model::application::CommandOptionType;
+
+use crate::ai::NameResolver;
+use crate::commands::{CommandName, MakaiCommand};
+use crate::context::MakaiContext;
+use crate::facts::{self, MAX_USER_FACTS};
+use crate::words::same_word;
+
+/// How many of the channel's latest messages `/fact extract` reads
+const EXTRACTION_MESSAGES: usize = 200;
+
+pub struct FactCommand;
+
+#[async_trait]
+impl MakaiCommand for FactCommand {
+    fn name(&self) -> CommandName {
+        "fact"
+    }
+
+    fn register(&self) -> CreateCommand {
+        let user = |description: &str, required: bool| {
+            CreateCommandOption::new(CommandOptionType::User, "user", description)
+                .required(required)
+        };
+        let fact = |description: &str, autocomplete: bool| {
+            CreateCommandOption::new(CommandOptionType::String, "fact", description)
+                .required(true)
+                .set_autocomplete(autocomplete)
+        };
+
+        CreateCommand::new(self.name())
+            .add_context(InteractionContext::Guild)
+            .description("Manage what makai knows about the members of this server")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "add",
+                    "Teach makai something about a member",
+                )
+                .add_sub_option(user("Who the fact is about", true))
+                .add_sub_option(fact("eg. `hates pineapple on pizza`", false)),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "list",
+                    "Show what makai knows about a member",
+                )
+                .add_sub_option(user("Who to show facts about, yourself by default", false)),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "remove",
+                    "Remove a fact, needs to be about you, added by you, or Manage Server",
+                )
+                .add_sub_option(user("Who the fact is about", true))
+                .add_sub_option(fact("The fact to remove", true)),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "approve",
+                    "Approve a proposed fact, needs to be about you or Manage Server",
+                )
+                .add_sub_option(user("Who the fact is about", true))
+                .add_sub_option(fact("The proposed fact to approve", true)),
+            )
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "extract",
+                "Propose facts from this channel's memory, needs Manage Server",
+            ))
+    }
+
+    async fn run(
+        &self,
+        bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        cmd: &CommandInteraction,
+    ) -> anyhow::Result<()> {
+        let guild_id = cmd.guild_id.context("Fact command used outside of guild")?;
+
+        let options = cmd.data.options();
+        let Some(ResolvedOption {
+            name: subcommand,
+            value: ResolvedValue::SubCommand(options),
+            ..
+        }) = options.first()
+        else {
+            bail!("Find subcommand")
+        };
+
+        let can_manage_guild = cmd
+            .member
+            .as_ref()
+            .and_then(|it| it.permissions)
+            .is_some_and(|it| it.manage_guild());
+        let user = options
+            .iter()
+            .find_map(|it| match it.value {
+                ResolvedValue::User(user, _) if it.name == "user" => Some(user.id),
+                _ => None,
+            })
+            .unwrap_or(cmd.user.id);
+        let fact = options.iter().find_map(|it| match it.value {
+            ResolvedValue::String(fact) if it.name == "fact" => Some(fact),
+            _ => None,
+        });
+        let guild = bot_ctx.guild(&guild_id).await;
+
+        let content = match *subcommand {
+            "extract" => {
+                let defer = CreateInteractionResponse::Defer(
+                    CreateInteractionResponseMessage::new().ephemeral(true),
+                );
+                cmd.create_response(&discord_ctx.http, defer)
+                    .await
+                    .context("Cannot defer command")?;
+
+                let content = if !can_manage_guild {
+                    "You need the Manage Server permission to extract facts".to_string()
+                } else {
+                    let names = NameResolver {
+                        cache: &discord_ctx.cache,
+                        guild_id: cmd.guild_id,
+                    };
+                    match self.extract(bot_ctx, names, cmd).await {
+                        Ok(content) => content,
+                        Err(err) => format!("Cannot extract facts: {err:#}"),
+                    }
+                };
+
+                let follow_up = CreateInteractionResponseFollowup::new()
+                    .ephemeral(true)
+                    .content(content);
+                cmd.create_followup(&discord_ctx.http, follow_up)
+                    .await
+                    .context("Cannot followup command")?;
+
+                return Ok(());
+            }
+            "list" => {
+                let facts = guild.facts().await;
+                let facts = facts.facts(&user);
+
+                if facts.is_empty() {
+                    format!("Makai doesn't know anything about <@{user}> yet")
+                } else {
+                    let list = facts
+                        .iter()
+                        .map(|it| {
+                            let pending = if it.pending { " (pending)" } else { "" };
+                            format!("- {}{pending}", it.text)
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    format!(
+                        "What makai knows about <@{user}> ({}/{MAX_USER_FACTS}):\n{list}",
+                        facts.len()
+                    )
+                }
+            }
+            "add" => {
+                let fact = fact.context("Find fact")?;
+                // Facts about someone else wait for them (or an admin) to agree
+                let pending = user != cmd.user.id && !can_manage_guild;
+
+                match guild
+                    .update_facts(|it| it.add(user, fact, Some(cmd.user.id), pending))
+                    .await
+                {
+                    Ok(true) if pending => format!(
+                        "Proposed `{fact}`, <@{user}> or an admin needs to approve it with \
+                         `/fact approve`"
+                    ),
+                    Ok(true) => format!("Makai now knows `{fact}` about <@{user}>"),
+                    Ok(false) => "Makai already knows that".to_string(),
+                    Err(err) => format!("Cannot add `{fact}`: {err:#}"),
+                }
+            }
+            "remove" => {
+                let fact = fact.context("Find fact")?;
+                let allowed = |added_by: Option<UserId>| {
+                    can_manage_guild || user == cmd.user.id || added_by == Some(cmd.user.id)
+                };
+
+                let removed = guild
+                    .update_facts(|it| {
+                        let added_by = it
+                            .facts(&user)
+                            .iter()
+                            .find(|it| same_word(&it.text, fact))
+                            .map(|it| it.added_by)?;
+                        if !allowed(added_by) {
+                            return Some(false);
+                        }
+
+                        Some(it.remove(&user, fact).is_some())
+                    })
+                    .await;
+                match removed {
+                    Some(true) => format!("Removed `{fact}`"),
+                    Some(false) => "You can only remove facts about you or added by you, \
+                                    or with the Manage Server permission"
+                        .to_string(),
+                    None => format!("Makai doesn't know `{fact}` about <@{user}>"),
+                }
+            }
+            "approve" => {
+                let fact = fact.context("Find fact")?;
+
+                if !can_manage_guild && user != cmd.user.id {
+                    "Only the member it's about or an admin can approve a fact".to_string()
+                } else if guild.update_facts(|it| it.approve(&user, fact)).await {
+                    format!("Approved `{fact}`")
+                } else {
+                    format!("`{fact}` isn't waiting for approval")
+                }
+            }
+            _ => bail!("Unknown subcommand `{subcommand}`"),
+        };
+
+        let message = CreateInteractionResponseMessage::new()
+            .ephemeral(true)
+            .content(content);
+        cmd.create_response(
+            &discord_ctx.http,
+            CreateInteractionResponse::Message(message),
+        )
+        .await
+        .context("Cannot ack command")?;
+
+        Ok(())
+    }
+
+    async fn autocomplete(
+        &self,
+        bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        cmd: &CommandInteraction,
+    ) -> anyhow::Result<()> {
+        let Some(guild_id) = cmd.guild_id else {
+            return Ok(());
+        };
+        let Some(autocomplete) = cmd.data.autocomplete() else {
+            return Ok(());
+        };
+
+        // Users aren't resolved while autocompleting, so read the raw option
+        let Some(CommandDataOptionValue::SubCommand(options)) =
+            cmd.data.options.first().map(|it| &it.value)
+        else {
+            return Ok(());
+        };
+        let user = options
+            .iter()
+            .find_map(|it| match it.value {
+                CommandDataOptionValue::User(user) if it.name == "user" => Some(user),
+                _ => None,
+            })
+            .unwrap_or(cmd.user.id);
+        let pending_only = cmd
+            .data
+            .options
+            .first()
+            .is_some_and(|it| it.name == "approve");
+
+        let facts = bot_ctx.guild(&guild_id).await.facts().await;
+        let query = autocomplete.value.to_lowercase();
+        let choices = facts
+            .facts(&user)
+            .iter()
+            .filter(|it| !pending_only || it.pending)
+            .filter(|it| it.text.to_lowercase().contains(&query))
+            .take(25)
+            .map(|it| AutocompleteChoice::new(it.text.clone(), it.text.clone()))
+            .collect();
+
+        cmd.create_response(
+            &discord_ctx.http,
+            CreateInteractionResponse::Autocomplete(
+                CreateAutocompleteResponse::new().set_choices(choices),
+            ),
+        )
+        .await
+        .context("Cannot respond to autocomplete")?;
+
+        Ok(())
+    }
+}
+
+impl FactCommand {
+    /// Proposes facts from the channel's memory, returning the message to show the user
+    async fn extract(
+        &self,
+        bot_ctx: &MakaiContext,
+        names: NameResolver<'_>,
+        cmd: &CommandInteraction,
+    ) -> anyhow::Result<String> {
+        let guild_id = cmd.guild_id.context("Fact command used outside of guild")?;
+        let settings = bot_ctx
+            .generation_settings(cmd.guild_id, &cmd.channel_id)
+            .await
+            .context("Resolve settings")?;
+
+        let mut messages = bot_ctx
+            .memory(cmd.guild_id, &cmd.channel_id)
+            .await
+            .recent_messages(EXTRACTION_MESSAGES)
+            .await;
+        // Opted out users never have facts extracted, even from messages they `Remember`ed
+        let mut opted_out = Vec::new();
+        for id in messages.iter().filter_map(|it| it.author_id()) {
+            if bot_ctx.is_opted_out(&id).await {
+                opted_out.push(id);
+            }
+        }
+        messages.retain(|it| !it.author_id().is_some_and(|id| opted_out.contains(&id)));
+
+        let extracted = facts::extract_facts(&settings, names, &messages).await?;
+
+        let guild = bot_ctx.guild(&guild_id).await;
+        let proposed = guild
+            .update_facts(|facts| {
+                extracted
+                    .into_iter()
+                    .filter(|(user, fact)| matches!(facts.add(*user, fact, None, true), Ok(true)))
+                    .map(|(user, fact)| format!("- <@{user}> {fact}"))
+                    .collect::<Vec<_>>()
+            })
+            .await;
+
+        if proposed.is_empty() {
+            Ok("Found nothing new worth remembering".to_string())
+        } else {
+            Ok(format!(
+                "Proposed {} facts, the members they're about or an admin can approve them with \
+                 `/fact approve`:\n{}",
+                proposed.len(),
+                proposed.join("\n")
+            ))
+        }
+    }
+}
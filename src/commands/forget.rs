@@ -0,0 +1,70 @@
+use anyhow::Context as _;
+use async_trait::async_trait;
+use serenity::all::{
+    CommandInteraction, CommandType, Context, CreateInteractionResponse,
+    CreateInteractionResponseMessage, InteractionContext, InteractionResponseFlags,
+};
+use serenity::builder::CreateCommand;
+
+use crate::commands::{CommandName, MakaiCommand, check_memory_access, describe_memory};
+use crate::context::MakaiContext;
+
+pub struct ForgetCommand;
+
+#[async_trait]
+impl MakaiCommand for ForgetCommand {
+    fn name(&self) -> CommandName {
+        "Forget this message"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .kind(CommandType::Message)
+            .add_context(InteractionContext::BotDm)
+            .add_context(InteractionContext::Guild)
+            .add_context(InteractionContext::PrivateChannel)
+    }
+
+    async fn run(
+        &self,
+        bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        cmd: &CommandInteraction,
+    ) -> anyhow::Result<()> {
+        let message_id = cmd
+            .data
+            .target_id
+            .context("Get message from command")?
+            .to_message_id();
+
+        let memory = bot_ctx.memory_id(cmd.guild_id, &cmd.channel_id).await;
+        let permissions = cmd.member.as_ref().and_then(|it| it.permissions);
+
+        let content = match check_memory_access(&memory, permissions) {
+            Ok(()) => {
+                let found = bot_ctx
+                    .memory(cmd.guild_id, &cmd.channel_id)
+                    .await
+                    .forget(message_id)
+                    .await;
+
+                let description = describe_memory(&memory, cmd.channel_id);
+                if found {
+                    format!("Removed message from {description}")
+                } else {
+                    format!("That message isn't in {description}")
+                }
+            }
+            Err(reason) => reason.to_string(),
+        };
+        let message = CreateInteractionResponseMessage::default()
+            .flags(InteractionResponseFlags::EPHEMERAL)
+            .content(content);
+        let response = CreateInteractionResponse::Message(message);
+        cmd.create_response(&discord_ctx.http, response)
+            .await
+            .context("Cannot ack command")?;
+
+        Ok(())
+    }
+}
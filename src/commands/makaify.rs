@@ -0,0 +1,356 @@
+use std::env;
+
+use anyhow::{Context as _, bail};
+use async_trait::async_trait;
+use chrono::Utc;
+use serenity::all::{
+    ActionRowComponent, ChannelId, CommandInteraction, CommandType, Context, CreateActionRow,
+    CreateInputText, CreateInteractionResponse, CreateInteractionResponseMessage, CreateModal,
+    GuildId, InputTextStyle, InteractionContext, ModalInteraction, ResolvedOption, ResolvedValue,
+    User,
+};
+use serenity::builder::{CreateCommand, CreateCommandOption};
+use serenity::model::application::CommandOptionType;
+
+use crate::ai::{self, FollowUpTarget, MakaiMessage, MessageKind, MessageSender};
+use crate::commands::{CommandName, MakaiCommand};
+use crate::context::MakaiContext;
+use crate::mentions::MentionSource;
+use crate::moderation::Moderator;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Intensity {
+    Mild,
+    #[default]
+    Medium,
+    Heavy,
+    Unreadable,
+}
+
+impl Intensity {
+    const ALL: [Intensity; 4] = [
+        Intensity::Mild,
+        Intensity::Medium,
+        Intensity::Heavy,
+        Intensity::Unreadable,
+    ];
+
+    fn id(&self) -> &'static str {
+        match self {
+            Intensity::Mild => "mild",
+            Intensity::Medium => "medium",
+            Intensity::Heavy => "heavy",
+            Intensity::Unreadable => "unreadable",
+        }
+    }
+
+    fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|it| it.id() == id)
+    }
+
+    fn instructions(&self) -> &'static str {
+        match self {
+            Intensity::Mild => {
+                "mild. Only a few playful misspellings and makain phrases, it should still read easily."
+            }
+            Intensity::Medium => {
+                "medium. Misspell most longer words and sprinkle in makain phrases, like the example."
+            }
+            Intensity::Heavy => {
+                "heavy. Misspell almost every word, mangle the grammar and lean hard on makain phrases."
+            }
+            Intensity::Unreadable => {
+                "unreadable. Go completely overboard, the meaning should only barely survive."
+            }
+        }
+    }
+}
+
+pub struct MakaifyCommand;
+
+#[async_trait]
+impl MakaiCommand for MakaifyCommand {
+    fn name(&self) -> CommandName {
+        "makaify"
+    }
+
+    fn register(&self) -> CreateCommand {
+        let intensity = Intensity::ALL.iter().fold(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "intensity",
+                "How makain the result should be",
+            ),
+            |option, it| option.add_string_choice(it.id(), it.id()),
+        );
+
+        CreateCommand::new(self.name())
+            .add_context(InteractionContext::BotDm)
+            .add_context(InteractionContext::Guild)
+            .add_context(InteractionContext::PrivateChannel)
+            .description("Rewrite text in makain")
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::String,
+                "text",
+                "The text to rewrite, leave empty to write a longer text",
+            ))
+            .add_option(intensity)
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "remember",
+                "Add the result to this channel's memory",
+            ))
+    }
+
+    async fn run(
+        &self,
+        bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        cmd: &CommandInteraction,
+    ) -> anyhow::Result<()> {
+        let options = cmd.data.options();
+        let text = options.iter().find_map(|it| match it.value {
+            ResolvedValue::String(text) if it.name == "text" => Some(text),
+            _ => None,
+        });
+        let intensity = options
+            .iter()
+            .find_map(|it| match it.value {
+                ResolvedValue::String(intensity) if it.name == "intensity" => {
+                    Intensity::from_id(intensity)
+                }
+                _ => None,
+            })
+            .unwrap_or_default();
+        let remember = options.iter().any(|it| {
+            matches!(
+                it,
+                ResolvedOption {
+                    name: "remember",
+                    value: ResolvedValue::Boolean(true),
+                    ..
+                }
+            )
+        });
+
+        let Some(text) = text else {
+            // No text given, ask for it in a modal so longer drafts are easier to write
+            let modal = CreateModal::new(
+                format!("{}:{}:{remember}", self.name(), intensity.id()),
+                "Makaify",
+            )
+            .components(vec![CreateActionRow::InputText(
+                CreateInputText::new(InputTextStyle::Paragraph, "Text", "text")
+                    .placeholder("email to my professor..."),
+            )]);
+            cmd.create_response(&discord_ctx.http, CreateInteractionResponse::Modal(modal))
+                .await
+                .context("Cannot open modal")?;
+
+            return Ok(());
+        };
+
+        let defer = CreateInteractionResponse::Defer(CreateInteractionResponseMessage::default());
+        cmd.create_response(&discord_ctx.http, defer)
+            .await
+            .context("Cannot defer command")?;
+
+        let text = MentionSource {
+            cache: &discord_ctx.cache,
+            guild_id: cmd.guild_id,
+            users: &[],
+            channels: &[],
+        }
+        .resolve(text);
+
+        makaify(
+            bot_ctx,
+            discord_ctx,
+            MakaifyRequest {
+                target: cmd.into(),
+                user: &cmd.user,
+                nickname: cmd.member.as_ref().and_then(|it| it.nick.clone()),
+                guild_id: cmd.guild_id,
+                channel_id: cmd.channel_id,
+                intensity,
+                remember,
+            },
+            text,
+        )
+        .await
+    }
+
+    async fn modal(
+        &self,
+        bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        modal: &ModalInteraction,
+    ) -> anyhow::Result<()> {
+        let mut parts = modal.data.custom_id.split(':').skip(1);
+        let intensity = parts
+            .next()
+            .and_then(Intensity::from_id)
+            .unwrap_or_default();
+        let remember = parts.next() == Some("true");
+
+        let Some(text) = modal
+            .data
+            .components
+            .iter()
+            .flat_map(|it| it.components.iter())
+            .find_map(|it| match it {
+                ActionRowComponent::InputText(input) if input.custom_id == "text" => {
+                    input.value.clone()
+                }
+                _ => None,
+            })
+        else {
+            bail!("Find text")
+        };
+
+        let defer = CreateInteractionResponse::Defer(CreateInteractionResponseMessage::default());
+        modal
+            .create_response(&discord_ctx.http, defer)
+            .await
+            .context("Cannot defer modal")?;
+
+        makaify(
+            bot_ctx,
+            discord_ctx,
+            MakaifyRequest {
+                target: modal.into(),
+                user: &modal.user,
+                nickname: modal.member.as_ref().and_then(|it| it.nick.clone()),
+                guild_id: modal.guild_id,
+                channel_id: modal.channel_id,
+                intensity,
+                remember,
+            },
+            text,
+        )
+        .await
+    }
+}
+
+pub struct MakaifyMessageCommand;
+
+#[async_trait]
+impl MakaiCommand for MakaifyMessageCommand {
+    fn name(&self) -> CommandName {
+        "Makaify"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .kind(CommandType::Message)
+            .add_context(InteractionContext::BotDm)
+            .add_context(InteractionContext::Guild)
+            .add_context(InteractionContext::PrivateChannel)
+    }
+
+    async fn run(
+        &self,
+        bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        cmd: &CommandInteraction,
+    ) -> anyhow::Result<()> {
+        let defer = CreateInteractionResponse::Defer(CreateInteractionResponseMessage::default());
+        cmd.create_response(&discord_ctx.http, defer)
+            .await
+            .context("Cannot defer command")?;
+
+        let user = bot_ctx
+            .user()
+            .await
+            .context("Got command before user is known")?;
+
+        // Only used to read the message, it never gets stored
+        let message = MakaiMessage::from_message_command(&discord_ctx.cache, user.id, cmd)
+            .context("Get message from command")?;
+
+        makaify(
+            bot_ctx,
+            discord_ctx,
+            MakaifyRequest {
+                target: cmd.into(),
+                user: &cmd.user,
+                nickname: cmd.member.as_ref().and_then(|it| it.nick.clone()),
+                guild_id: cmd.guild_id,
+                channel_id: cmd.channel_id,
+                intensity: Intensity::default(),
+                remember: false,
+            },
+            message.content,
+        )
+        .await
+    }
+}
+
+struct MakaifyRequest<'a> {
+    target: FollowUpTarget<'a>,
+    user: &'a User,
+    /// The user's nickname in the guild, remembered with the request
+    nickname: Option<String>,
+    guild_id: Option<GuildId>,
+    channel_id: ChannelId,
+    intensity: Intensity,
+    remember: bool,
+}
+
+async fn makaify(
+    bot_ctx: &MakaiContext,
+    discord_ctx: Context,
+    request: MakaifyRequest<'_>,
+    text: String,
+) -> anyhow::Result<()> {
+    let settings = bot_ctx
+        .generation_settings(request.guild_id, &request.channel_id)
+        .await
+        .context("Resolve settings")?;
+    let prompt_file =
+        env::var("LLM_MAKAIFY_PROMPT_FILE").unwrap_or_else(|_| "./makaify_prompt.txt".to_string());
+    let system = ai::load_prompt(&prompt_file, &settings.words)
+        .await?
+        .replace("{INTENSITY}", request.intensity.instructions());
+
+    let moderator = Moderator::for_channel(&discord_ctx, request.guild_id, request.channel_id)
+        .await
+        .context("Load moderator")?;
+    let response = ai::run_oneshot(&settings, &moderator, system, text.clone())
+        .await
+        .context("Run LLM")?;
+
+    // Blocked inputs never make it into memory, same as with `/chat`
+    let exchange = (request.remember && !response.refused).then(rand::random);
+    let channel = bot_ctx.memory(request.guild_id, &request.channel_id).await;
+    if let Some(exchange) = exchange {
+        channel
+            .add_message(MakaiMessage {
+                message_id: None,
+                timestamp: Utc::now(),
+                sender: MessageSender::user(request.user, request.nickname),
+                content: format!("Makaify this: {text}"),
+                exchange: Some(exchange),
+                kind: MessageKind::Message,
+                inherited: false,
+            })
+            .await;
+        channel
+            .add_message_with_retention(
+                MakaiMessage::from_assistant_response(response.response.clone(), Some(exchange)),
+                &settings.retention,
+            )
+            .await;
+    }
+
+    let guild_settings = bot_ctx.guild_settings(request.guild_id).await;
+    let sent = response
+        .send_follow_up(discord_ctx, request.target, guild_settings.mention_policy)
+        .await
+        .context("Send Follow up")?;
+    if let Some(exchange) = exchange {
+        channel.set_reply_message_id(exchange, sent.id).await;
+    }
+
+    Ok(())
+}
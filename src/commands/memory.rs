@@ -0,0 +1,575 @@
+use anyhow::{Context as _, bail};
+use async_trait::async_trait;
+use serenity::all::{
+    Attachment, ButtonStyle, ChannelId, CommandInteraction, ComponentInteraction, Context,
+    CreateActionRow, CreateAttachment, CreateButton, CreateEmbed, CreateEmbedFooter,
+    CreateInteractionResponse, CreateInteractionResponseFollowup, CreateInteractionResponseMessage,
+    InteractionContext, ResolvedOption, ResolvedValue,
+};
+use serenity::builder::{CreateCommand, CreateCommandOption};
+use serenity::model::application::CommandOptionType;
+
+use crate::ai::NameResolver;
+use crate::commands::{CommandName, MakaiCommand, check_memory_access};
+use crate::context::MakaiContext;
+use crate::context::serde::{ImportMode, MakaiContextChannelSerde};
+use crate::schema;
+use crate::settings::MemoryScope;
+
+const PAGE_SIZE: usize = 10;
+const CONTENT_PREVIEW_LEN: usize = 120;
+/// Searches are kept in the page buttons' custom ids, which are limited to 100 chars
+const MAX_SEARCH_LEN: u16 = 60;
+const MAX_IMPORT_SIZE: u32 = 8 * 1024 * 1024;
+const MAX_IMPORT_MESSAGES: usize = 10_000;
+
+pub struct MemoryCommand;
+
+#[async_trait]
+impl MakaiCommand for MemoryCommand {
+    fn name(&self) -> CommandName {
+        "memory"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .add_context(InteractionContext::BotDm)
+            .add_context(InteractionContext::Guild)
+            .add_context(InteractionContext::PrivateChannel)
+            .description("Manage what makai remembers in this channel")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "view",
+                    "Page through what makai remembers in this channel",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "search",
+                        "Only show messages containing this text",
+                    )
+                    .max_length(MAX_SEARCH_LEN),
+                ),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "export",
+                    "Download what makai remembers in this channel",
+                )
+                .add_sub_option(CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "transcript",
+                    "Also include a readable markdown transcript",
+                )),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "import",
+                    "Load memory from an exported file",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Attachment,
+                        "file",
+                        "A file from /memory export",
+                    )
+                    .required(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "mode",
+                        "Whether to merge with or replace the current memory",
+                    )
+                    .add_string_choice("Merge", "merge")
+                    .add_string_choice("Replace", "replace"),
+                ),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "scope",
+                    "Show or change which memory this channel uses",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "scope",
+                        "Which memory to use, leave empty to show the current one",
+                    )
+                    .add_string_choice("This channel only", "channel")
+                    .add_string_choice("Shared with the whole server", "guild")
+                    .add_string_choice("Shared with a group of channels", "group"),
+                )
+                .add_sub_option(CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "group",
+                    "The group to link this channel to, for the group scope",
+                ))
+                .add_sub_option(CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "server",
+                    "Change the server default instead of this channel",
+                )),
+            )
+    }
+
+    async fn run(
+        &self,
+        bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        cmd: &CommandInteraction,
+    ) -> anyhow::Result<()> {
+        let options = cmd.data.options();
+        let Some(ResolvedOption {
+            name: subcommand,
+            value: ResolvedValue::SubCommand(options),
+            ..
+        }) = options.first()
+        else {
+            bail!("Find subcommand")
+        };
+
+        // Everything but `scope` works on whatever memory this channel uses, which may be
+        // shared with channels the invoker can't see
+        let memory = bot_ctx.memory_id(cmd.guild_id, &cmd.channel_id).await;
+        let permissions = cmd.member.as_ref().and_then(|it| it.permissions);
+
+        match *subcommand {
+            "view" => {
+                let search = options.iter().find_map(|it| match it.value {
+                    ResolvedValue::String(search) if it.name == "search" => Some(search),
+                    _ => None,
+                });
+
+                let page = match check_memory_access(&memory, permissions) {
+                    Ok(()) => {
+                        self.render_page(
+                            bot_ctx,
+                            NameResolver {
+                                cache: &discord_ctx.cache,
+                                guild_id: cmd.guild_id,
+                            },
+                            cmd.channel_id,
+                            0,
+                            search.unwrap_or_default(),
+                        )
+                        .await
+                    }
+                    Err(reason) => CreateInteractionResponseMessage::new().content(reason),
+                }
+                .ephemeral(true);
+                cmd.create_response(&discord_ctx.http, CreateInteractionResponse::Message(page))
+                    .await
+                    .context("Cannot respond to command")?;
+            }
+            "export" => {
+                let transcript = options.iter().any(|it| {
+                    matches!(
+                        it,
+                        ResolvedOption {
+                            name: "transcript",
+                            value: ResolvedValue::Boolean(true),
+                            ..
+                        }
+                    )
+                });
+
+                if let Err(reason) = check_memory_access(&memory, permissions) {
+                    let message = CreateInteractionResponseMessage::new()
+                        .ephemeral(true)
+                        .content(reason);
+                    cmd.create_response(
+                        &discord_ctx.http,
+                        CreateInteractionResponse::Message(message),
+                    )
+                    .await
+                    .context("Cannot respond to command")?;
+
+                    return Ok(());
+                }
+
+                let channel = bot_ctx.memory(cmd.guild_id, &cmd.channel_id).await;
+                let memory = MakaiContextChannelSerde::from((*channel).clone()).without_inherited();
+                let json = schema::encode_channel(&memory)?;
+
+                let mut message = CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content(format!("Exported {} messages", memory.messages().count()))
+                    .add_file(CreateAttachment::bytes(
+                        json,
+                        format!("makai_memory_{}.json", cmd.channel_id),
+                    ));
+                if transcript {
+                    message = message.add_file(CreateAttachment::bytes(
+                        markdown_transcript(
+                            &memory,
+                            NameResolver {
+                                cache: &discord_ctx.cache,
+                                guild_id: cmd.guild_id,
+                            },
+                        ),
+                        format!("makai_memory_{}.md", cmd.channel_id),
+                    ));
+                }
+
+                cmd.create_response(
+                    &discord_ctx.http,
+                    CreateInteractionResponse::Message(message),
+                )
+                .await
+                .context("Cannot respond to command")?;
+            }
+            "import" => {
+                let defer = CreateInteractionResponse::Defer(
+                    CreateInteractionResponseMessage::new().ephemeral(true),
+                );
+                cmd.create_response(&discord_ctx.http, defer)
+                    .await
+                    .context("Cannot defer command")?;
+
+                let Some(file) = options.iter().find_map(|it| match it.value {
+                    ResolvedValue::Attachment(file) if it.name == "file" => Some(file),
+                    _ => None,
+                }) else {
+                    bail!("Find file")
+                };
+                let mode = match options.iter().find_map(|it| match it.value {
+                    ResolvedValue::String(mode) if it.name == "mode" => Some(mode),
+                    _ => None,
+                }) {
+                    Some("replace") => ImportMode::Replace,
+                    _ => ImportMode::Merge,
+                };
+
+                // Merging only adds, but replacing throws away what everyone else said
+                let can_replace =
+                    cmd.guild_id.is_none() || permissions.is_some_and(|it| it.manage_channels());
+
+                let content = if let Err(reason) = check_memory_access(&memory, permissions) {
+                    reason.to_string()
+                } else if mode == ImportMode::Replace && !can_replace {
+                    "You need the Manage Channels permission to replace memory".to_string()
+                } else {
+                    match load_import(file).await {
+                        Ok(memory) => {
+                            let count = memory.messages().count();
+                            bot_ctx
+                                .memory(cmd.guild_id, &cmd.channel_id)
+                                .await
+                                .import(memory, mode)
+                                .await;
+
+                            match mode {
+                                ImportMode::Merge => format!("Merged {count} messages into memory"),
+                                ImportMode::Replace => {
+                                    format!("Replaced memory with {count} messages")
+                                }
+                            }
+                        }
+                        Err(err) => format!("Cannot import `{}`: {err:#}", file.filename),
+                    }
+                };
+
+                let follow_up = CreateInteractionResponseFollowup::new()
+                    .ephemeral(true)
+                    .content(content);
+                cmd.create_followup(&discord_ctx.http, follow_up)
+                    .await
+                    .context("Cannot followup command")?;
+            }
+            "scope" => {
+                let content = match self.apply_scope(bot_ctx, cmd, options).await {
+                    Ok(content) => content,
+                    Err(err) => format!("{err:#}"),
+                };
+
+                let message = CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content(content);
+                cmd.create_response(
+                    &discord_ctx.http,
+                    CreateInteractionResponse::Message(message),
+                )
+                .await
+                .context("Cannot respond to command")?;
+            }
+            _ => bail!("Unknown subcommand `{subcommand}`"),
+        }
+
+        Ok(())
+    }
+
+    async fn component(
+        &self,
+        bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        component: &ComponentInteraction,
+    ) -> anyhow::Result<()> {
+        // `memory:page:<page>:<search>`
+        let mut parts = component.data.custom_id.splitn(4, ':').skip(1);
+        let (Some("page"), Some(page), search) = (parts.next(), parts.next(), parts.next()) else {
+            bail!("Unknown component `{}`", component.data.custom_id);
+        };
+        let page = page.parse::<usize>().context("Parse page")?;
+
+        let memory = bot_ctx
+            .memory_id(component.guild_id, &component.channel_id)
+            .await;
+        let permissions = component.member.as_ref().and_then(|it| it.permissions);
+        if let Err(reason) = check_memory_access(&memory, permissions) {
+            let message = CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .content(reason);
+            component
+                .create_response(
+                    &discord_ctx.http,
+                    CreateInteractionResponse::Message(message),
+                )
+                .await
+                .context("Cannot respond to component")?;
+
+            return Ok(());
+        }
+
+        let page = self
+            .render_page(
+                bot_ctx,
+                NameResolver {
+                    cache: &discord_ctx.cache,
+                    guild_id: component.guild_id,
+                },
+                component.channel_id,
+                page,
+                search.unwrap_or_default(),
+            )
+            .await;
+        component
+            .create_response(
+                &discord_ctx.http,
+                CreateInteractionResponse::UpdateMessage(page),
+            )
+            .await
+            .context("Cannot update memory page")?;
+
+        Ok(())
+    }
+}
+
+impl MemoryCommand {
+    /// Shows or changes the channel's (or server's) memory scope, returning the message to show
+    /// the user. User facing problems are returned as errors so they can be shown as is
+    async fn apply_scope(
+        &self,
+        bot_ctx: &MakaiContext,
+        cmd: &CommandInteraction,
+        options: &[ResolvedOption<'_>],
+    ) -> anyhow::Result<String> {
+        let Some(guild_id) = cmd.guild_id else {
+            bail!("Memory can only be shared in a server");
+        };
+        let option = |name: &str| {
+            options.iter().find_map(|it| match it.value {
+                ResolvedValue::String(value) if it.name == name => Some(value),
+                _ => None,
+            })
+        };
+        let server = options.iter().any(|it| {
+            matches!(
+                it,
+                ResolvedOption {
+                    name: "server",
+                    value: ResolvedValue::Boolean(true),
+                    ..
+                }
+            )
+        });
+
+        let Some(scope) = option("scope") else {
+            let scope = bot_ctx.memory_scope(cmd.guild_id, &cmd.channel_id).await;
+            return Ok(format!("This channel uses {}", describe_scope(&scope)));
+        };
+        let scope = match (scope, option("group")) {
+            ("group", None) => bail!("Pick a group to link this channel to"),
+            ("group", Some(group)) => format!("group:{group}").parse::<MemoryScope>()?,
+            (scope, _) => scope.parse::<MemoryScope>()?,
+        };
+
+        let permissions = cmd.member.as_ref().and_then(|it| it.permissions);
+        if server {
+            if !permissions.is_some_and(|it| it.manage_guild()) {
+                bail!("You need the Manage Server permission to change server defaults");
+            }
+
+            let description = describe_scope(&scope);
+            bot_ctx
+                .guild(&guild_id)
+                .await
+                .update_settings(|it| it.channel_defaults.memory_scope = Some(scope))
+                .await;
+
+            Ok(format!(
+                "Channels without their own scope now use {description}"
+            ))
+        } else {
+            // Same as `/config set memory_scope`, joining a shared memory reads what the other
+            // channels in it wrote
+            if !permissions.is_some_and(|it| it.manage_channels()) {
+                bail!("You need the Manage Channels permission to change this channel's scope");
+            }
+
+            let description = describe_scope(&scope);
+            bot_ctx
+                .channel(&cmd.channel_id)
+                .await
+                .update_settings(|it| it.memory_scope = Some(scope))
+                .await;
+
+            // Nothing is moved between memories, switching back finds the old one as it was
+            Ok(format!(
+                "This channel now uses {description}, what was remembered before stays with the \
+                 previous scope"
+            ))
+        }
+    }
+
+    async fn render_page(
+        &self,
+        bot_ctx: &MakaiContext,
+        names: NameResolver<'_>,
+        channel_id: ChannelId,
+        page: usize,
+        search: &str,
+    ) -> CreateInteractionResponseMessage {
+        let guild_id = names.guild_id;
+        let query = search.to_lowercase();
+
+        let messages = bot_ctx
+            .memory(guild_id, &channel_id)
+            .await
+            .messages()
+            .await
+            .into_iter()
+            .rev()
+            .filter(|it| {
+                query.is_empty()
+                    || it.content.to_lowercase().contains(&query)
+                    || names
+                        .sender_name(&it.sender)
+                        .to_lowercase()
+                        .contains(&query)
+            })
+            .collect::<Vec<_>>();
+
+        let pages = messages.len().div_ceil(PAGE_SIZE).max(1);
+        let page = page.min(pages - 1);
+
+        let description = messages
+            .iter()
+            .skip(page * PAGE_SIZE)
+            .take(PAGE_SIZE)
+            .map(|it| {
+                format!(
+                    "**{}** · <t:{}:R>\n{}",
+                    names.sender_name(&it.sender),
+                    it.timestamp.timestamp(),
+                    preview(&it.content)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let description = if description.is_empty() {
+            "Nothing remembered".to_string()
+        } else {
+            description
+        };
+
+        let title = if search.is_empty() {
+            match bot_ctx.memory_scope(guild_id, &channel_id).await {
+                MemoryScope::Channel => "Memory for this channel".to_string(),
+                MemoryScope::Guild => "Memory for this server".to_string(),
+                MemoryScope::Group(group) => format!("Memory for the `{group}` group"),
+            }
+        } else {
+            format!("Memory matching `{search}`")
+        };
+        let embed = CreateEmbed::new()
+            .title(title)
+            .description(description)
+            .footer(CreateEmbedFooter::new(format!(
+                "Page {}/{pages} · {} messages",
+                page + 1,
+                messages.len()
+            )));
+
+        let button = |label: &str, target: usize, disabled: bool| {
+            CreateButton::new(format!("{}:page:{target}:{search}", self.name()))
+                .label(label)
+                .style(ButtonStyle::Secondary)
+                .disabled(disabled)
+        };
+        let buttons = CreateActionRow::Buttons(vec![
+            button("Prev", page.saturating_sub(1), page == 0),
+            button("Next", page + 1, page + 1 >= pages),
+        ]);
+
+        CreateInteractionResponseMessage::new()
+            .embed(embed)
+            .components(vec![buttons])
+    }
+}
+
+fn describe_scope(scope: &MemoryScope) -> String {
+    match scope {
+        MemoryScope::Channel => "its own memory".to_string(),
+        MemoryScope::Guild => "the server's shared memory".to_string(),
+        MemoryScope::Group(group) => format!("the memory of the `{group}` group"),
+    }
+}
+
+/// Downloads and validates an exported memory file
+async fn load_import(file: &Attachment) -> anyhow::Result<MakaiContextChannelSerde> {
+    if file.size > MAX_IMPORT_SIZE {
+        bail!("File is larger than {} MiB", MAX_IMPORT_SIZE / 1024 / 1024);
+    }
+
+    let data = file.download().await.context("Download file")?;
+    let memory = schema::decode_channel(&data).context("Not a valid memory export")?;
+
+    let count = memory.messages().count();
+    if count > MAX_IMPORT_MESSAGES {
+        bail!("File has {count} messages, at most {MAX_IMPORT_MESSAGES} can be imported");
+    }
+
+    Ok(memory)
+}
+
+fn markdown_transcript(memory: &MakaiContextChannelSerde, names: NameResolver) -> String {
+    memory.messages().fold(String::new(), |mut acc, it| {
+        acc.push_str(&format!(
+            "**{}** ({}):\n{}\n\n",
+            names.sender_name(&it.sender),
+            it.timestamp.format("%Y-%m-%d %H:%M UTC"),
+            it.content
+        ));
+        acc
+    })
+}
+
+fn preview(content: &str) -> String {
+    let content = content.replace('\n', " ");
+
+    if content.chars().count() > CONTENT_PREVIEW_LEN {
+        let mut preview = content
+            .chars()
+            .take(CONTENT_PREVIEW_LEN)
+            .collect::<String>();
+        preview.push('…');
+        preview
+    } else {
+        content
+    }
+}
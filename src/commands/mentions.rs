@@ -0,0 +1,76 @@
+use anyhow::{Context as _, bail};
+use async_trait::async_trait;
+use serenity::all::{
+    CommandInteraction, Context, CreateInteractionResponse, CreateInteractionResponseMessage,
+    InteractionContext, InteractionResponseFlags, Permissions, ResolvedOption, ResolvedValue,
+};
+use serenity::builder::{CreateCommand, CreateCommandOption};
+use serenity::model::application::CommandOptionType;
+
+use crate::commands::{CommandName, MakaiCommand};
+use crate::context::MakaiContext;
+use crate::mentions::MentionPolicy;
+
+pub struct MentionsCommand;
+
+#[async_trait]
+impl MakaiCommand for MentionsCommand {
+    fn name(&self) -> CommandName {
+        "mentions"
+    }
+
+    fn register(&self) -> CreateCommand {
+        let policy = MentionPolicy::ALL.iter().fold(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "policy",
+                "Who makai is allowed to ping",
+            )
+            .required(true),
+            |option, policy| option.add_string_choice(policy.description(), policy.id()),
+        );
+
+        CreateCommand::new(self.name())
+            .add_context(InteractionContext::Guild)
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .description("Configure who makai is allowed to ping in this server")
+            .add_option(policy)
+    }
+
+    async fn run(
+        &self,
+        bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        cmd: &CommandInteraction,
+    ) -> anyhow::Result<()> {
+        let guild_id = cmd
+            .guild_id
+            .context("Mentions command used outside of guild")?;
+
+        let options = cmd.data.options();
+        let Some(ResolvedOption {
+            value: ResolvedValue::String(policy),
+            ..
+        }) = options.iter().find(|it| it.name == "policy")
+        else {
+            bail!("Find policy")
+        };
+        let policy = MentionPolicy::from_id(policy).context("Parse mention policy")?;
+
+        bot_ctx
+            .guild(&guild_id)
+            .await
+            .update_settings(|it| it.mention_policy = policy)
+            .await;
+
+        let message = CreateInteractionResponseMessage::default()
+            .flags(InteractionResponseFlags::EPHEMERAL)
+            .content(format!("Mention policy set to: {}", policy.description()));
+        let response = CreateInteractionResponse::Message(message);
+        cmd.create_response(&discord_ctx.http, response)
+            .await
+            .context("Cannot ack command")?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+use anyhow::{Context as _, bail};
+use async_trait::async_trait;
+use serenity::all::{
+    ChannelType, CommandInteraction, Context, CreateInteractionResponse,
+    CreateInteractionResponseFollowup, CreateInteractionResponseMessage, InteractionContext,
+    ResolvedOption, ResolvedValue,
+};
+use serenity::builder::{CreateCommand, CreateCommandOption};
+use serenity::model::application::CommandOptionType;
+use tracing::error;
+
+use crate::commands::{CommandName, MakaiCommand};
+use crate::context::MakaiContext;
+use crate::personas::{self, PersonaSource};
+use crate::settings::is_valid_persona_name;
+
+pub struct PersonaCommand;
+
+#[async_trait]
+impl MakaiCommand for PersonaCommand {
+    fn name(&self) -> CommandName {
+        "persona"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .add_context(InteractionContext::Guild)
+            .description("Personas built from a friend's own messages")
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "consent",
+                "Allow admins to build a persona from your messages",
+            ))
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "revoke",
+                "Withdraw your consent and delete every persona built from your messages",
+            ))
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "build",
+                    "Build a persona from a user's messages, needs Manage Server",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::User,
+                        "user",
+                        "Whose messages to learn from, they must have consented",
+                    )
+                    .required(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "name",
+                        "The persona name, for `/config set persona`",
+                    )
+                    .required(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "source",
+                        "Where to read their messages from",
+                    )
+                    .add_string_choice("Makai's memory", "memory")
+                    .add_string_choice("Channel history", "history"),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Channel,
+                        "channel",
+                        "The channel to read history from, this one by default",
+                    )
+                    .channel_types(vec![ChannelType::Text, ChannelType::PublicThread]),
+                ),
+            )
+    }
+
+    async fn run(
+        &self,
+        bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        cmd: &CommandInteraction,
+    ) -> anyhow::Result<()> {
+        let guild_id = cmd
+            .guild_id
+            .context("Persona command used outside of guild")?;
+
+        let options = cmd.data.options();
+        let Some(ResolvedOption {
+            name: subcommand,
+            value: ResolvedValue::SubCommand(options),
+            ..
+        }) = options.first()
+        else {
+            bail!("Find subcommand")
+        };
+
+        let defer = CreateInteractionResponse::Defer(
+            CreateInteractionResponseMessage::new().ephemeral(true),
+        );
+        cmd.create_response(&discord_ctx.http, defer)
+            .await
+            .context("Cannot defer command")?;
+
+        let content = match *subcommand {
+            "consent" => {
+                if bot_ctx.grant_persona_consent(cmd.user.id).await {
+                    "Admins can now build personas from your messages. \
+                     Use `/persona revoke` at any time to delete them"
+                        .to_string()
+                } else {
+                    "You already consented, use `/persona revoke` to withdraw".to_string()
+                }
+            }
+            "revoke" => match bot_ctx.revoke_persona_consent(&cmd.user.id).await {
+                Some(consent) => {
+                    for persona in &consent.personas {
+                        if let Err(err) = personas::delete_persona(bot_ctx, persona).await {
+                            error!("Cannot delete persona `{persona}`: {err:?}");
+                        }
+                    }
+
+                    format!(
+                        "Consent withdrawn, deleted {} personas built from your messages",
+                        consent.personas.len()
+                    )
+                }
+                None => "You haven't consented to personas".to_string(),
+            },
+            "build" => {
+                let can_manage_guild = cmd
+                    .member
+                    .as_ref()
+                    .and_then(|it| it.permissions)
+                    .is_some_and(|it| it.manage_guild());
+
+                let Some(user) = options.iter().find_map(|it| match it.value {
+                    ResolvedValue::User(user, _) if it.name == "user" => Some(user),
+                    _ => None,
+                }) else {
+                    bail!("Find user")
+                };
+                let Some(name) = options.iter().find_map(|it| match it.value {
+                    ResolvedValue::String(name) if it.name == "name" => Some(name),
+                    _ => None,
+                }) else {
+                    bail!("Find name")
+                };
+                let channel = options
+                    .iter()
+                    .find_map(|it| match &it.value {
+                        ResolvedValue::Channel(channel) if it.name == "channel" => Some(channel.id),
+                        _ => None,
+                    })
+                    .unwrap_or(cmd.channel_id);
+                let source = match options.iter().find_map(|it| match it.value {
+                    ResolvedValue::String(source) if it.name == "source" => Some(source),
+                    _ => None,
+                }) {
+                    Some("history") => PersonaSource::History(channel),
+                    _ => PersonaSource::Memory,
+                };
+
+                if !can_manage_guild {
+                    "You need the Manage Server permission to build personas".to_string()
+                } else if !is_valid_persona_name(name) {
+                    "Persona names may only contain letters, numbers, `-` and `_`".to_string()
+                } else {
+                    let settings = bot_ctx
+                        .generation_settings(cmd.guild_id, &cmd.channel_id)
+                        .await
+                        .context("Resolve settings")?;
+
+                    match personas::build_persona(
+                        bot_ctx,
+                        &discord_ctx,
+                        &settings,
+                        guild_id,
+                        user.id,
+                        name,
+                        source,
+                    )
+                    .await
+                    {
+                        Ok(built) => format!(
+                            "Built persona `{name}` from {} messages with {} phrases, \
+                             use it with `/config set persona {name}`",
+                            built.sample_size, built.phrases
+                        ),
+                        Err(err) => format!("Cannot build persona: {err:#}"),
+                    }
+                }
+            }
+            _ => bail!("Unknown subcommand `{subcommand}`"),
+        };
+
+        let follow_up = CreateInteractionResponseFollowup::new()
+            .ephemeral(true)
+            .content(content);
+        cmd.create_followup(&discord_ctx.http, follow_up)
+            .await
+            .context("Cannot followup command")?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+use anyhow::{Context as _, bail};
+use async_trait::async_trait;
+use serenity::all::{
+    CommandInteraction, Context, CreateInteractionResponse, CreateInteractionResponseMessage,
+    InteractionContext, InteractionResponseFlags, ResolvedOption, ResolvedValue,
+};
+use serenity::builder::{CreateCommand, CreateCommandOption};
+use serenity::model::application::CommandOptionType;
+
+use crate::commands::{CommandName, MakaiCommand};
+use crate::context::MakaiContext;
+
+pub struct PrivacyCommand;
+
+#[async_trait]
+impl MakaiCommand for PrivacyCommand {
+    fn name(&self) -> CommandName {
+        "privacy"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .add_context(InteractionContext::BotDm)
+            .add_context(InteractionContext::Guild)
+            .add_context(InteractionContext::PrivateChannel)
+            .description("Control whether makai listens to your messages")
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "opt-out",
+                "Never remember your messages unless you use Remember on them",
+            ))
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "opt-in",
+                "Let makai remember your messages in channels with passive listening",
+            ))
+    }
+
+    async fn run(
+        &self,
+        bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        cmd: &CommandInteraction,
+    ) -> anyhow::Result<()> {
+        let options = cmd.data.options();
+        let Some(ResolvedOption {
+            name: subcommand,
+            value: ResolvedValue::SubCommand(_),
+            ..
+        }) = options.first()
+        else {
+            bail!("Find subcommand")
+        };
+
+        let content = match *subcommand {
+            "opt-out" => {
+                bot_ctx.set_opted_out(cmd.user.id, true).await;
+                "Makai will no longer listen to your messages"
+            }
+            "opt-in" => {
+                bot_ctx.set_opted_out(cmd.user.id, false).await;
+                "Makai can listen to your messages again in channels with passive listening"
+            }
+            _ => bail!("Unknown subcommand `{subcommand}`"),
+        };
+
+        let message = CreateInteractionResponseMessage::default()
+            .flags(InteractionResponseFlags::EPHEMERAL)
+            .content(content);
+        let response = CreateInteractionResponse::Message(message);
+        cmd.create_response(&discord_ctx.http, response)
+            .await
+            .context("Cannot ack command")?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::Context as _;
+use async_trait::async_trait;
+use serenity::all::{
+    CommandInteraction, CommandType, Context, CreateInteractionResponse,
+    CreateInteractionResponseFollowup, CreateInteractionResponseMessage, InteractionContext,
+};
+use serenity::builder::CreateCommand;
+
+use crate::ai::MakaiMessage;
+use crate::commands::{CommandName, MakaiCommand};
+use crate::context::MakaiContext;
+use crate::moderation::Moderator;
+use crate::reactions;
+
+pub struct ReactCommand;
+
+#[async_trait]
+impl MakaiCommand for ReactCommand {
+    fn name(&self) -> CommandName {
+        "React as Makai"
+    }
+
+    fn register(&self) -> CreateCommand {
+        // Makai can only react where it can see the message, so not in other people's dms
+        CreateCommand::new(self.name())
+            .kind(CommandType::Message)
+            .add_context(InteractionContext::BotDm)
+            .add_context(InteractionContext::Guild)
+    }
+
+    async fn run(
+        &self,
+        bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        cmd: &CommandInteraction,
+    ) -> anyhow::Result<()> {
+        let defer = CreateInteractionResponse::Defer(
+            CreateInteractionResponseMessage::default().ephemeral(true),
+        );
+        cmd.create_response(&discord_ctx.http, defer)
+            .await
+            .context("Cannot defer command")?;
+
+        let user = bot_ctx
+            .user()
+            .await
+            .context("Got command before user is known")?;
+        let message = cmd
+            .data
+            .resolved
+            .messages
+            .values()
+            .next()
+            .context("Get message from command")?;
+        let target = MakaiMessage::from_message(&discord_ctx.cache, user.id, cmd.guild_id, message);
+
+        let settings = bot_ctx
+            .generation_settings(cmd.guild_id, &cmd.channel_id)
+            .await
+            .context("Resolve settings")?;
+        let moderator = Moderator::for_channel(&discord_ctx, cmd.guild_id, cmd.channel_id)
+            .await
+            .context("Load moderator")?;
+        let channel = bot_ctx.memory(cmd.guild_id, &cmd.channel_id).await;
+
+        let reactions = reactions::pick_reactions(
+            &discord_ctx,
+            &settings,
+            &moderator,
+            &channel,
+            cmd.guild_id,
+            &target,
+        )
+        .await
+        .context("Pick reactions")?;
+        let added = reactions::react(&discord_ctx, &channel, message, reactions).await;
+
+        let content = if added.is_empty() {
+            "Makai had no reaction".to_string()
+        } else {
+            let emoji = added
+                .iter()
+                .map(|it| it.name.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            format!("Reacted with {emoji}")
+        };
+        let follow_up = CreateInteractionResponseFollowup::new()
+            .ephemeral(true)
+            .content(content);
+        cmd.create_followup(&discord_ctx.http, follow_up)
+            .await
+            .context("Cannot followup command")?;
+
+        Ok(())
+    }
+}
@@ -46,13 +46,14 @@ impl MakaiCommand for RememberCommand {
             .await
             .context("Got command before user is known")?;
 
-        let message =
-            MakaiMessage::from_message_command(user.id, cmd).context("Get message from command")?;
+        let message = MakaiMessage::from_message_command(&discord_ctx.cache, user.id, cmd)
+            .context("Get message from command")?;
 
+        let retention = bot_ctx.retention(cmd.guild_id, &cmd.channel_id).await;
         bot_ctx
-            .channel(&cmd.channel_id)
+            .memory(cmd.guild_id, &cmd.channel_id)
             .await
-            .add_message(message)
+            .add_message_with_retention(message, &retention)
             .await;
 
         Ok(())
@@ -6,9 +6,10 @@ use serenity::all::{
 };
 use serenity::builder::CreateCommand;
 
-use crate::ai::{self, MakaiMessage};
+use crate::ai::{self, MakaiMessage, NameResolver};
 use crate::commands::{CommandName, MakaiCommand};
 use crate::context::MakaiContext;
+use crate::moderation::Moderator;
 
 pub struct ReplyCommand;
 
@@ -42,16 +43,32 @@ impl MakaiCommand for ReplyCommand {
             .await
             .context("Got command before user is known")?;
 
-        let message =
-            MakaiMessage::from_message_command(user.id, cmd).context("Get message from command")?;
+        let message = MakaiMessage::from_message_command(&discord_ctx.cache, user.id, cmd)
+            .context("Get message from command")?;
 
-        let response = ai::run_llm(&*bot_ctx.channel(&cmd.channel_id).await, message)
+        let moderator = Moderator::for_channel(&discord_ctx, cmd.guild_id, cmd.channel_id)
+            .await
+            .context("Load moderator")?;
+        let settings = bot_ctx
+            .generation_settings(cmd.guild_id, &cmd.channel_id)
+            .await
+            .context("Resolve settings")?;
+        let channel = bot_ctx.memory(cmd.guild_id, &cmd.channel_id).await;
+        let names = NameResolver {
+            cache: &discord_ctx.cache,
+            guild_id: cmd.guild_id,
+        };
+        let response = ai::run_llm(&channel, &settings, &moderator, names, message)
             .await
             .context("Run LLM")?;
-        response
-            .send_follow_up(discord_ctx, cmd)
+        let guild_settings = bot_ctx.guild_settings(cmd.guild_id).await;
+        let sent = response
+            .send_follow_up(discord_ctx, cmd, guild_settings.mention_policy)
             .await
             .context("Send Follow up")?;
+        if let Some(exchange) = response.exchange {
+            channel.set_reply_message_id(exchange, sent.id).await;
+        }
 
         Ok(())
     }
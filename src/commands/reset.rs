@@ -1,14 +1,12 @@
-use anyhow::Context as _;
 use async_trait::async_trait;
 use serenity::all::{
-    CommandInteraction, CommandType, Context, CreateInteractionResponse,
-    CreateInteractionResponseMessage, InteractionContext, InteractionResponseFlags,
+    CommandInteraction, Context, CreateInteractionResponse, CreateInteractionResponseMessage,
+    InteractionContext, InteractionResponseFlags,
 };
 use serenity::builder::CreateCommand;
 use tracing::error;
 
-use crate::ai::MakaiMessage;
-use crate::commands::{CommandName, MakaiCommand};
+use crate::commands::{CommandName, MakaiCommand, check_memory_access, describe_memory};
 use crate::context::MakaiContext;
 
 pub struct ResetCommand;
@@ -33,15 +31,29 @@ impl MakaiCommand for ResetCommand {
         discord_ctx: Context,
         cmd: &CommandInteraction,
     ) -> anyhow::Result<()> {
+        let memory = bot_ctx.memory_id(cmd.guild_id, &cmd.channel_id).await;
+        let permissions = cmd.member.as_ref().and_then(|it| it.permissions);
+        let allowed = check_memory_access(&memory, permissions);
+
+        let content = match allowed {
+            Ok(()) => format!("Cleared {}", describe_memory(&memory, cmd.channel_id)),
+            Err(reason) => reason.to_string(),
+        };
         let message = CreateInteractionResponseMessage::default()
             .flags(InteractionResponseFlags::EPHEMERAL)
-            .content("Memory for this channel has been cleared");
+            .content(content);
         let response = CreateInteractionResponse::Message(message);
         if let Err(err) = cmd.create_response(&discord_ctx.http, response).await {
             error!("Cannot ack command: {err:?}");
         }
 
-        bot_ctx.channel(&cmd.channel_id).await.clear().await;
+        if allowed.is_ok() {
+            bot_ctx
+                .memory(cmd.guild_id, &cmd.channel_id)
+                .await
+                .clear()
+                .await;
+        }
 
         Ok(())
     }
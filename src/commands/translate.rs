@@ -0,0 +1,139 @@
+use std::env;
+
+use anyhow::{Context as _, bail};
+use async_trait::async_trait;
+use serenity::all::{
+    CommandInteraction, CommandType, Context, CreateInteractionResponse,
+    CreateInteractionResponseMessage, InteractionContext, ResolvedOption, ResolvedValue,
+};
+use serenity::builder::{CreateCommand, CreateCommandOption};
+use serenity::model::application::CommandOptionType;
+
+use crate::ai::{self, MakaiMessage};
+use crate::commands::{CommandName, MakaiCommand};
+use crate::context::MakaiContext;
+use crate::mentions::{MentionPolicy, MentionSource};
+use crate::moderation::Moderator;
+
+pub struct TranslateCommand;
+
+#[async_trait]
+impl MakaiCommand for TranslateCommand {
+    fn name(&self) -> CommandName {
+        "translate"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .add_context(InteractionContext::BotDm)
+            .add_context(InteractionContext::Guild)
+            .add_context(InteractionContext::PrivateChannel)
+            .description("Translate makain into plain english")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "text",
+                    "The makain to translate",
+                )
+                .required(true),
+            )
+    }
+
+    async fn run(
+        &self,
+        bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        cmd: &CommandInteraction,
+    ) -> anyhow::Result<()> {
+        let options = cmd.data.options();
+        let Some(ResolvedOption {
+            value: ResolvedValue::String(text),
+            ..
+        }) = options.iter().find(|it| it.name == "text")
+        else {
+            bail!("Find text")
+        };
+
+        let text = MentionSource {
+            cache: &discord_ctx.cache,
+            guild_id: cmd.guild_id,
+            users: &[],
+            channels: &[],
+        }
+        .resolve(text);
+
+        translate(bot_ctx, discord_ctx, cmd, text).await
+    }
+}
+
+pub struct TranslateMessageCommand;
+
+#[async_trait]
+impl MakaiCommand for TranslateMessageCommand {
+    fn name(&self) -> CommandName {
+        "Translate from Makain"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .kind(CommandType::Message)
+            .add_context(InteractionContext::BotDm)
+            .add_context(InteractionContext::Guild)
+            .add_context(InteractionContext::PrivateChannel)
+    }
+
+    async fn run(
+        &self,
+        bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        cmd: &CommandInteraction,
+    ) -> anyhow::Result<()> {
+        let user = bot_ctx
+            .user()
+            .await
+            .context("Got command before user is known")?;
+
+        // Only used to read the message, it never gets stored
+        let message = MakaiMessage::from_message_command(&discord_ctx.cache, user.id, cmd)
+            .context("Get message from command")?;
+
+        translate(bot_ctx, discord_ctx, cmd, message.content).await
+    }
+}
+
+async fn translate(
+    bot_ctx: &MakaiContext,
+    discord_ctx: Context,
+    cmd: &CommandInteraction,
+    text: String,
+) -> anyhow::Result<()> {
+    let defer = CreateInteractionResponse::Defer(
+        CreateInteractionResponseMessage::default().ephemeral(true),
+    );
+    cmd.create_response(&discord_ctx.http, defer)
+        .await
+        .context("Cannot defer command")?;
+
+    let settings = bot_ctx
+        .generation_settings(cmd.guild_id, &cmd.channel_id)
+        .await
+        .context("Resolve settings")?;
+    let prompt_file = env::var("LLM_TRANSLATE_PROMPT_FILE")
+        .unwrap_or_else(|_| "./translate_prompt.txt".to_string());
+
+    let system = ai::load_prompt(&prompt_file, &settings.words).await?;
+
+    let moderator = Moderator::for_channel(&discord_ctx, cmd.guild_id, cmd.channel_id)
+        .await
+        .context("Load moderator")?;
+    let response = ai::run_oneshot(&settings, &moderator, system, text)
+        .await
+        .context("Run LLM")?
+        .ephemeral();
+    response
+        .send_follow_up(discord_ctx, cmd, MentionPolicy::Nobody)
+        .await
+        .context("Send Follow up")?;
+
+    Ok(())
+}
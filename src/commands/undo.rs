@@ -0,0 +1,63 @@
+use anyhow::Context as _;
+use async_trait::async_trait;
+use serenity::all::{
+    CommandInteraction, Context, CreateInteractionResponse, CreateInteractionResponseMessage,
+    InteractionContext, InteractionResponseFlags,
+};
+use serenity::builder::CreateCommand;
+
+use crate::commands::{CommandName, MakaiCommand, check_memory_access};
+use crate::context::MakaiContext;
+
+pub struct UndoCommand;
+
+#[async_trait]
+impl MakaiCommand for UndoCommand {
+    fn name(&self) -> CommandName {
+        "undo"
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .add_context(InteractionContext::BotDm)
+            .add_context(InteractionContext::Guild)
+            .add_context(InteractionContext::PrivateChannel)
+            .description("Forget the last message and reply")
+    }
+
+    async fn run(
+        &self,
+        bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        cmd: &CommandInteraction,
+    ) -> anyhow::Result<()> {
+        let memory = bot_ctx.memory_id(cmd.guild_id, &cmd.channel_id).await;
+        let permissions = cmd.member.as_ref().and_then(|it| it.permissions);
+
+        let content = match check_memory_access(&memory, permissions) {
+            Ok(()) => {
+                let removed = bot_ctx
+                    .memory(cmd.guild_id, &cmd.channel_id)
+                    .await
+                    .undo()
+                    .await;
+
+                if removed == 0 {
+                    "Nothing to undo".to_string()
+                } else {
+                    format!("Forgot the last exchange ({removed} messages)")
+                }
+            }
+            Err(reason) => reason.to_string(),
+        };
+        let message = CreateInteractionResponseMessage::default()
+            .flags(InteractionResponseFlags::EPHEMERAL)
+            .content(content);
+        let response = CreateInteractionResponse::Message(message);
+        cmd.create_response(&discord_ctx.http, response)
+            .await
+            .context("Cannot ack command")?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,421 @@
+use anyhow::{Context as _, bail};
+use async_trait::async_trait;
+use serenity::all::{
+    Attachment, AutocompleteChoice, ButtonStyle, CommandInteraction, ComponentInteraction, Context,
+    CreateActionRow, CreateAutocompleteResponse, CreateButton, CreateEmbed, CreateEmbedFooter,
+    CreateInteractionResponse, CreateInteractionResponseFollowup, CreateInteractionResponseMessage,
+    GuildId, InteractionContext, ResolvedOption, ResolvedValue,
+};
+use serenity::builder::{CreateCommand, CreateCommandOption};
+use serenity::model::application::CommandOptionType;
+
+use crate::commands::{CommandName, MakaiCommand};
+use crate::context::MakaiContext;
+use crate::words::MAX_GUILD_WORDS;
+
+const PAGE_SIZE: usize = 20;
+const MAX_IMPORT_SIZE: u32 = 256 * 1024;
+
+pub struct WordsCommand;
+
+#[async_trait]
+impl MakaiCommand for WordsCommand {
+    fn name(&self) -> CommandName {
+        "words"
+    }
+
+    fn register(&self) -> CreateCommand {
+        let word = |description: &str, autocomplete: bool| {
+            CreateCommandOption::new(CommandOptionType::String, "word", description)
+                .required(true)
+                .set_autocomplete(autocomplete)
+        };
+
+        CreateCommand::new(self.name())
+            .add_context(InteractionContext::Guild)
+            .description("Manage this server's makain words")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "add",
+                    "Teach makai a word or phrase",
+                )
+                .add_sub_option(word("The word or phrase to add", false)),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "remove",
+                    "Remove a word or suggestion, needs Manage Server",
+                )
+                .add_sub_option(word("The word or phrase to remove", true)),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "list",
+                    "Page through this server's words",
+                )
+                .add_sub_option(CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "pending",
+                    "Show suggestions waiting for approval instead",
+                )),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "import",
+                    "Add words from a text file, one per line, needs Manage Server",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Attachment,
+                        "file",
+                        "A text file with one word or phrase per line",
+                    )
+                    .required(true),
+                ),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "approve",
+                    "Approve a suggested word, needs Manage Server",
+                )
+                .add_sub_option(word("The suggestion to approve", true)),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "approval",
+                    "Choose whether suggested words need approval, needs Manage Server",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "required",
+                        "Whether words added by members need approval",
+                    )
+                    .required(true),
+                ),
+            )
+    }
+
+    async fn run(
+        &self,
+        bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        cmd: &CommandInteraction,
+    ) -> anyhow::Result<()> {
+        let guild_id = cmd
+            .guild_id
+            .context("Words command used outside of guild")?;
+
+        let options = cmd.data.options();
+        let Some(ResolvedOption {
+            name: subcommand,
+            value: ResolvedValue::SubCommand(options),
+            ..
+        }) = options.first()
+        else {
+            bail!("Find subcommand")
+        };
+
+        let can_manage_guild = cmd
+            .member
+            .as_ref()
+            .and_then(|it| it.permissions)
+            .is_some_and(|it| it.manage_guild());
+        let word = options.iter().find_map(|it| match it.value {
+            ResolvedValue::String(word) if it.name == "word" => Some(word),
+            _ => None,
+        });
+        let guild = bot_ctx.guild(&guild_id).await;
+
+        let content = match *subcommand {
+            "list" => {
+                let pending = options.iter().any(|it| {
+                    matches!(
+                        it,
+                        ResolvedOption {
+                            name: "pending",
+                            value: ResolvedValue::Boolean(true),
+                            ..
+                        }
+                    )
+                });
+
+                let page = self
+                    .render_page(bot_ctx, guild_id, 0, pending)
+                    .await
+                    .ephemeral(true);
+                cmd.create_response(&discord_ctx.http, CreateInteractionResponse::Message(page))
+                    .await
+                    .context("Cannot respond to command")?;
+
+                return Ok(());
+            }
+            "import" => {
+                let defer = CreateInteractionResponse::Defer(
+                    CreateInteractionResponseMessage::new().ephemeral(true),
+                );
+                cmd.create_response(&discord_ctx.http, defer)
+                    .await
+                    .context("Cannot defer command")?;
+
+                let Some(file) = options.iter().find_map(|it| match it.value {
+                    ResolvedValue::Attachment(file) if it.name == "file" => Some(file),
+                    _ => None,
+                }) else {
+                    bail!("Find file")
+                };
+
+                let content = if !can_manage_guild {
+                    "You need the Manage Server permission to import words".to_string()
+                } else {
+                    match load_import(file).await {
+                        Ok(text) => {
+                            let (added, skipped) = guild.update_words(|it| it.import(&text)).await;
+                            format!("Imported {added} words, skipped {skipped}")
+                        }
+                        Err(err) => format!("Cannot import `{}`: {err:#}", file.filename),
+                    }
+                };
+
+                let follow_up = CreateInteractionResponseFollowup::new()
+                    .ephemeral(true)
+                    .content(content);
+                cmd.create_followup(&discord_ctx.http, follow_up)
+                    .await
+                    .context("Cannot followup command")?;
+
+                return Ok(());
+            }
+            "add" => {
+                let word = word.context("Find word")?;
+                let pending = guild.settings().await.word_approval && !can_manage_guild;
+
+                match guild.update_words(|it| it.add(word, pending)).await {
+                    Ok(true) if pending => {
+                        format!("Suggested `{word}`, an admin needs to approve it")
+                    }
+                    Ok(true) => format!("Added `{word}`"),
+                    Ok(false) => format!("`{word}` is already on the list"),
+                    Err(err) => format!("Cannot add `{word}`: {err:#}"),
+                }
+            }
+            "remove" => {
+                let word = word.context("Find word")?;
+
+                if !can_manage_guild {
+                    "You need the Manage Server permission to remove words".to_string()
+                } else if guild.update_words(|it| it.remove(word)).await {
+                    format!("Removed `{word}`")
+                } else {
+                    format!("`{word}` isn't on the list")
+                }
+            }
+            "approve" => {
+                let word = word.context("Find word")?;
+
+                if !can_manage_guild {
+                    "You need the Manage Server permission to approve words".to_string()
+                } else if guild.update_words(|it| it.approve(word)).await {
+                    format!("Approved `{word}`")
+                } else {
+                    format!("`{word}` isn't waiting for approval")
+                }
+            }
+            "approval" => {
+                let required = options.iter().any(|it| {
+                    matches!(
+                        it,
+                        ResolvedOption {
+                            name: "required",
+                            value: ResolvedValue::Boolean(true),
+                            ..
+                        }
+                    )
+                });
+
+                if !can_manage_guild {
+                    "You need the Manage Server permission to change word approval".to_string()
+                } else {
+                    guild
+                        .update_settings(|it| it.word_approval = required)
+                        .await;
+
+                    if required {
+                        "Words added by members now need approval".to_string()
+                    } else {
+                        "Words added by members are now used right away".to_string()
+                    }
+                }
+            }
+            _ => bail!("Unknown subcommand `{subcommand}`"),
+        };
+
+        let message = CreateInteractionResponseMessage::new()
+            .ephemeral(true)
+            .content(content);
+        cmd.create_response(
+            &discord_ctx.http,
+            CreateInteractionResponse::Message(message),
+        )
+        .await
+        .context("Cannot ack command")?;
+
+        Ok(())
+    }
+
+    async fn autocomplete(
+        &self,
+        bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        cmd: &CommandInteraction,
+    ) -> anyhow::Result<()> {
+        let Some(guild_id) = cmd.guild_id else {
+            return Ok(());
+        };
+        let Some(autocomplete) = cmd.data.autocomplete() else {
+            return Ok(());
+        };
+
+        let words = bot_ctx.guild(&guild_id).await.words().await;
+        let candidates = match cmd.data.options().first() {
+            Some(ResolvedOption {
+                name: "approve", ..
+            }) => words.pending().to_vec(),
+            _ => words
+                .words()
+                .iter()
+                .chain(words.pending())
+                .cloned()
+                .collect(),
+        };
+
+        let query = autocomplete.value.to_lowercase();
+        let choices = candidates
+            .into_iter()
+            .filter(|it| it.to_lowercase().contains(&query))
+            .take(25)
+            .map(|it| AutocompleteChoice::new(it.clone(), it))
+            .collect();
+
+        cmd.create_response(
+            &discord_ctx.http,
+            CreateInteractionResponse::Autocomplete(
+                CreateAutocompleteResponse::new().set_choices(choices),
+            ),
+        )
+        .await
+        .context("Cannot respond to autocomplete")?;
+
+        Ok(())
+    }
+
+    async fn component(
+        &self,
+        bot_ctx: &MakaiContext,
+        discord_ctx: Context,
+        component: &ComponentInteraction,
+    ) -> anyhow::Result<()> {
+        let guild_id = component
+            .guild_id
+            .context("Words component used outside of guild")?;
+
+        // `words:page:<page>:<pending>`
+        let mut parts = component.data.custom_id.split(':').skip(1);
+        let (Some("page"), Some(page), Some(pending)) = (parts.next(), parts.next(), parts.next())
+        else {
+            bail!("Unknown component `{}`", component.data.custom_id);
+        };
+        let page = page.parse::<usize>().context("Parse page")?;
+        let pending = pending.parse::<bool>().context("Parse pending")?;
+
+        let page = self.render_page(bot_ctx, guild_id, page, pending).await;
+        component
+            .create_response(
+                &discord_ctx.http,
+                CreateInteractionResponse::UpdateMessage(page),
+            )
+            .await
+            .context("Cannot update words page")?;
+
+        Ok(())
+    }
+}
+
+impl WordsCommand {
+    async fn render_page(
+        &self,
+        bot_ctx: &MakaiContext,
+        guild_id: GuildId,
+        page: usize,
+        pending: bool,
+    ) -> CreateInteractionResponseMessage {
+        let words = bot_ctx.guild(&guild_id).await.words().await;
+        let words = if pending {
+            words.pending()
+        } else {
+            words.words()
+        };
+
+        let pages = words.len().div_ceil(PAGE_SIZE).max(1);
+        let page = page.min(pages - 1);
+
+        let description = words
+            .iter()
+            .skip(page * PAGE_SIZE)
+            .take(PAGE_SIZE)
+            .map(|it| format!("- {it}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let description = if description.is_empty() {
+            "No words yet".to_string()
+        } else {
+            description
+        };
+
+        let title = if pending {
+            "Words waiting for approval"
+        } else {
+            "Words for this server"
+        };
+        let embed = CreateEmbed::new()
+            .title(title)
+            .description(description)
+            .footer(CreateEmbedFooter::new(format!(
+                "Page {}/{pages} · {}/{MAX_GUILD_WORDS} words",
+                page + 1,
+                words.len()
+            )));
+
+        let button = |label: &str, target: usize, disabled: bool| {
+            CreateButton::new(format!("{}:page:{target}:{pending}", self.name()))
+                .label(label)
+                .style(ButtonStyle::Secondary)
+                .disabled(disabled)
+        };
+        let buttons = CreateActionRow::Buttons(vec![
+            button("Prev", page.saturating_sub(1), page == 0),
+            button("Next", page + 1, page + 1 >= pages),
+        ]);
+
+        CreateInteractionResponseMessage::new()
+            .embed(embed)
+            .components(vec![buttons])
+    }
+}
+
+/// Downloads a word list, one word or phrase per line
+async fn load_import(file: &Attachment) -> anyhow::Result<String> {
+    if file.size > MAX_IMPORT_SIZE {
+        bail!("File is larger than {} KiB", MAX_IMPORT_SIZE / 1024);
+    }
+
+    let data = file.download().await.context("Download file")?;
+
+    String::from_utf8(data).context("Not a text file")
+}
@@ -1,23 +1,65 @@
 use std::{
-    collections::{BTreeMap, HashMap},
-    sync::Arc,
-    usize,
+    collections::{HashMap, HashSet},
+    sync::{Arc, OnceLock},
 };
 
+use ::serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
-use llm::chat::ChatMessage;
-use serenity::all::{ChannelId, User};
+use llm::chat::{ChatMessage, Usage};
+use serenity::all::{ChannelId, GuildId, MessageId, User, UserId};
 use tokio::sync::RwLock;
+use tracing::{debug, warn};
 
-use crate::ai::MakaiMessage;
+use crate::ai::{MakaiMessage, MessageKind, MessageSender, NameResolver};
+use crate::facts::UserFacts;
+use crate::message_log::{MessageKey, MessageLog};
+use crate::personas::PersonaConsent;
+use crate::retention::RetentionPolicy;
+use crate::settings::{
+    ChannelSettings, GenerationSettings, GuildSettings, MemoryScope, ThreadMemory,
+};
+use crate::store::{ChannelStore, GuildStore, MemoryId, Store};
+use crate::words::WordList;
+
+/// A memory shared by the channels of a guild with the `guild` or `group:<name>` memory scope
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SharedMemoryId {
+    pub guild: GuildId,
+    /// `None` for the whole guild's memory
+    pub group: Option<String>,
+}
 
 #[derive(Default)]
 pub struct MakaiContext {
     channels: RwLock<HashMap<ChannelId, Arc<MakaiContextChannel>>>,
+    guilds: RwLock<HashMap<GuildId, Arc<MakaiContextGuild>>>,
+    /// Memories shared by several channels, only their messages are used
+    shared: RwLock<HashMap<SharedMemoryId, Arc<MakaiContextChannel>>>,
+    /// Users whose messages are never recorded by passive listening
+    opted_out_users: RwLock<HashSet<UserId>>,
+    /// Users who agreed to have personas built from their messages
+    persona_consent: RwLock<HashMap<UserId, PersonaConsent>>,
     bot_user: RwLock<Option<User>>,
+    /// Where changes are written to as they happen, unset for detached copies
+    store: OnceLock<Arc<Store>>,
 }
 
 impl MakaiContext {
+    /// Starts writing every change through to `store`
+    pub async fn attach_store(&self, store: Arc<Store>) {
+        for (channel, ctx) in self.channels.read().await.iter() {
+            ctx.attach_store(store.clone(), MemoryId::Channel(*channel));
+        }
+        for (id, ctx) in self.shared.read().await.iter() {
+            ctx.attach_store(store.clone(), MemoryId::Shared(id.clone()));
+        }
+        for (guild, ctx) in self.guilds.read().await.iter() {
+            ctx.attach_store(store.clone(), *guild);
+        }
+
+        let _ = self.store.set(store);
+    }
+
     pub async fn channel(&self, channel: &ChannelId) -> Arc<MakaiContextChannel> {
         let read_lock = self.channels.read().await;
 
@@ -30,17 +72,407 @@ impl MakaiContext {
                 .write()
                 .await
                 .entry(*channel)
-                .or_default()
+                .or_insert_with(|| {
+                    let ctx = MakaiContextChannel::default();
+                    if let Some(store) = self.store.get() {
+                        ctx.attach_store(store.clone(), MemoryId::Channel(*channel));
+                    }
+                    Arc::new(ctx)
+                })
+                .clone()
+        }
+    }
+
+    pub async fn guild(&self, guild: &GuildId) -> Arc<MakaiContextGuild> {
+        let read_lock = self.guilds.read().await;
+
+        if let Some(guild) = read_lock.get(guild) {
+            guild.clone()
+        } else {
+            drop(read_lock);
+
+            self.guilds
+                .write()
+                .await
+                .entry(*guild)
+                .or_insert_with(|| {
+                    let ctx = MakaiContextGuild::default();
+                    if let Some(store) = self.store.get() {
+                        ctx.attach_store(store.clone(), *guild);
+                    }
+                    Arc::new(ctx)
+                })
                 .clone()
         }
     }
 
+    /// A memory shared by several channels, created empty the first time it is used
+    pub async fn shared_memory(&self, id: &SharedMemoryId) -> Arc<MakaiContextChannel> {
+        let read_lock = self.shared.read().await;
+
+        if let Some(memory) = read_lock.get(id) {
+            memory.clone()
+        } else {
+            drop(read_lock);
+
+            self.shared
+                .write()
+                .await
+                .entry(id.clone())
+                .or_insert_with(|| {
+                    let ctx = MakaiContextChannel::default();
+                    if let Some(store) = self.store.get() {
+                        ctx.attach_store(store.clone(), MemoryId::Shared(id.clone()));
+                    }
+                    Arc::new(ctx)
+                })
+                .clone()
+        }
+    }
+
+    /// The memory used in a channel. Threads sharing their parent's memory use whatever the
+    /// parent uses, which is its own memory unless its scope points at a shared one
+    pub async fn memory(
+        &self,
+        guild: Option<GuildId>,
+        channel: &ChannelId,
+    ) -> Arc<MakaiContextChannel> {
+        let ctx = self.channel_in(guild, channel).await;
+
+        match self.memory_id(guild, channel).await {
+            MemoryId::Shared(id) => self.shared_memory(&id).await,
+            MemoryId::Channel(source) if source == *channel => ctx,
+            MemoryId::Channel(source) => self.channel(&source).await,
+        }
+    }
+
+    /// Which memory [`Self::memory`] picks for a channel
+    pub async fn memory_id(&self, guild: Option<GuildId>, channel: &ChannelId) -> MemoryId {
+        let source = match self.channel(channel).await.parent().await {
+            Some(parent) if self.thread_memory(channel).await == ThreadMemory::Shared => parent,
+            _ => *channel,
+        };
+
+        match (guild, self.memory_scope(guild, &source).await) {
+            (Some(guild), MemoryScope::Guild) => {
+                MemoryId::Shared(SharedMemoryId { guild, group: None })
+            }
+            (Some(guild), MemoryScope::Group(group)) => MemoryId::Shared(SharedMemoryId {
+                guild,
+                group: Some(group),
+            }),
+            // Outside of guilds there's nothing to share with
+            _ => MemoryId::Channel(source),
+        }
+    }
+
+    /// Which memory the channel reads and writes, resolved against its guild's defaults
+    pub async fn memory_scope(&self, guild: Option<GuildId>, channel: &ChannelId) -> MemoryScope {
+        self.channel_settings(guild, channel)
+            .await
+            .memory_scope
+            .unwrap_or_default()
+    }
+
+    /// How a thread's memory relates to its parent's, falling back to the parent's setting
+    pub async fn thread_memory(&self, thread: &ChannelId) -> ThreadMemory {
+        let ctx = self.channel(thread).await;
+        let mut setting = ctx.settings().await.thread_memory;
+        if let Some(parent) = ctx.parent().await {
+            setting = setting.or(self.channel(&parent).await.settings().await.thread_memory);
+        }
+
+        match setting {
+            Some(setting) => setting,
+            None => self
+                .channel_settings(ctx.guild().await, thread)
+                .await
+                .or(&ChannelSettings::from_env().unwrap_or_default())
+                .thread_memory
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Settings for the given guild, or the defaults outside of guilds (eg. DMs)
+    pub async fn guild_settings(&self, guild: Option<GuildId>) -> GuildSettings {
+        match guild {
+            Some(guild) => self.guild(&guild).await.settings().await,
+            None => GuildSettings::default(),
+        }
+    }
+
+    /// Resolves the channel's settings against its guild's defaults and the global env vars
+    pub async fn generation_settings(
+        &self,
+        guild: Option<GuildId>,
+        channel: &ChannelId,
+    ) -> anyhow::Result<GenerationSettings> {
+        let words = self.guild_words(guild).await;
+        let facts = self.guild_facts(guild).await;
+        let channel = self.channel_in(guild, channel).await.settings().await;
+        let guild = self.guild_settings(guild).await;
+
+        Ok(GenerationSettings {
+            words,
+            facts,
+            ..GenerationSettings::resolve(channel, &guild)?
+        })
+    }
+
+    /// The guild's approved words, none outside of guilds
+    pub async fn guild_words(&self, guild: Option<GuildId>) -> Vec<String> {
+        match guild {
+            Some(guild) => self.guild(&guild).await.words().await.words().to_vec(),
+            None => Vec::new(),
+        }
+    }
+
+    /// The guild's facts about its members, none outside of guilds
+    pub async fn guild_facts(&self, guild: Option<GuildId>) -> UserFacts {
+        match guild {
+            Some(guild) => self.guild(&guild).await.facts().await,
+            None => UserFacts::default(),
+        }
+    }
+
+    /// The channel's settings with unset values filled in from its guild's defaults
+    pub async fn channel_settings(
+        &self,
+        guild: Option<GuildId>,
+        channel: &ChannelId,
+    ) -> ChannelSettings {
+        let channel = self.channel_in(guild, channel).await.settings().await;
+        let guild = self.guild_settings(guild).await;
+
+        channel.or(&guild.channel_defaults)
+    }
+
+    /// Like `channel`, but also records the guild the channel was seen in
+    async fn channel_in(
+        &self,
+        guild: Option<GuildId>,
+        channel: &ChannelId,
+    ) -> Arc<MakaiContextChannel> {
+        let ctx = self.channel(channel).await;
+        if let Some(guild) = guild {
+            ctx.set_guild(guild).await;
+        }
+
+        ctx
+    }
+
+    /// The channel's retention policy, resolved against its guild's defaults and the env vars
+    pub async fn retention(&self, guild: Option<GuildId>, channel: &ChannelId) -> RetentionPolicy {
+        Self::retention_from(self.channel_settings(guild, channel).await)
+    }
+
+    fn retention_from(settings: ChannelSettings) -> RetentionPolicy {
+        let settings = match ChannelSettings::from_env() {
+            Ok(env) => settings.or(&env),
+            Err(err) => {
+                warn!("Cannot read retention defaults from env: {err:?}");
+                settings
+            }
+        };
+
+        RetentionPolicy::from_settings(&settings)
+    }
+
+    /// Applies every channel's retention policy, returning how many messages were dropped
+    pub async fn prune(&self, now: DateTime<Utc>) -> usize {
+        let channels = self
+            .channels
+            .read()
+            .await
+            .iter()
+            .map(|(id, ctx)| (*id, ctx.clone()))
+            .collect::<Vec<_>>();
+
+        let mut dropped = 0;
+        for (id, ctx) in channels {
+            let retention = self.retention(ctx.guild().await, &id).await;
+            let count = ctx.prune(&retention, now).await;
+            if count > 0 {
+                debug!("Pruned {count} messages from {id}");
+            }
+
+            dropped += count;
+        }
+
+        // Shared memories have no settings of their own, so they follow the guild's defaults
+        let shared = self
+            .shared
+            .read()
+            .await
+            .iter()
+            .map(|(id, ctx)| (id.clone(), ctx.clone()))
+            .collect::<Vec<_>>();
+        for (id, ctx) in shared {
+            let settings = self.guild_settings(Some(id.guild)).await.channel_defaults;
+            let count = ctx.prune(&Self::retention_from(settings), now).await;
+            if count > 0 {
+                debug!("Pruned {count} messages from {id:?}");
+            }
+
+            dropped += count;
+        }
+
+        dropped
+    }
+
+    pub async fn is_opted_out(&self, user: &UserId) -> bool {
+        self.opted_out_users.read().await.contains(user)
+    }
+
+    /// Opts a user out of (or back into) passive listening, returns whether anything changed
+    pub async fn set_opted_out(&self, user: UserId, opted_out: bool) -> bool {
+        let mut opted_out_users = self.opted_out_users.write().await;
+
+        let changed = if opted_out {
+            opted_out_users.insert(user)
+        } else {
+            opted_out_users.remove(&user)
+        };
+        if let Some(store) = self.store.get() {
+            store.put_opted_out_users(&opted_out_users);
+        }
+
+        changed
+    }
+
+    pub async fn persona_consent(&self, user: &UserId) -> Option<PersonaConsent> {
+        self.persona_consent.read().await.get(user).cloned()
+    }
+
+    /// Records that a user agreed to have personas built from their messages, returns false if
+    /// they already had
+    pub async fn grant_persona_consent(&self, user: UserId) -> bool {
+        let mut consent = self.persona_consent.write().await;
+
+        if consent.contains_key(&user) {
+            return false;
+        }
+        consent.insert(user, PersonaConsent::new());
+        if let Some(store) = self.store.get() {
+            store.put_persona_consent(&consent);
+        }
+
+        true
+    }
+
+    /// Removes a user's consent, returning it so the personas derived from it can be deleted
+    pub async fn revoke_persona_consent(&self, user: &UserId) -> Option<PersonaConsent> {
+        let mut consent = self.persona_consent.write().await;
+
+        let removed = consent.remove(user);
+        if let Some(store) = self.store.get() {
+            store.put_persona_consent(&consent);
+        }
+
+        removed
+    }
+
+    /// Records a persona built from a user's messages, returns false if they haven't consented
+    pub async fn add_derived_persona(&self, user: &UserId, persona: String) -> bool {
+        let mut consent = self.persona_consent.write().await;
+
+        let Some(user_consent) = consent.get_mut(user) else {
+            return false;
+        };
+        if !user_consent.personas.contains(&persona) {
+            user_consent.personas.push(persona);
+        }
+        if let Some(store) = self.store.get() {
+            store.put_persona_consent(&consent);
+        }
+
+        true
+    }
+
+    /// The user a persona was built from, if any
+    pub async fn persona_owner(&self, persona: &str) -> Option<UserId> {
+        self.persona_consent
+            .read()
+            .await
+            .iter()
+            .find(|(_, it)| it.personas.iter().any(|it| it == persona))
+            .map(|(user, _)| *user)
+    }
+
+    /// Unsets a persona everywhere it's used, eg. after it's deleted
+    pub async fn clear_persona(&self, persona: &str) {
+        let uses_persona = |it: &ChannelSettings| it.persona.as_deref() == Some(persona);
+
+        for channel in self.channels.read().await.values() {
+            channel
+                .update_settings(|it| {
+                    if uses_persona(it) {
+                        it.persona = None;
+                    }
+                })
+                .await;
+        }
+        for guild in self.guilds.read().await.values() {
+            guild
+                .update_settings(|it| {
+                    if uses_persona(&it.channel_defaults) {
+                        it.channel_defaults.persona = None;
+                    }
+                })
+                .await;
+        }
+    }
+
+    /// A user's remembered messages, from the channels `in_channel` accepts and the guild's
+    /// shared memories
+    pub async fn user_messages(
+        &self,
+        user: UserId,
+        guild: GuildId,
+        in_channel: impl Fn(&ChannelId) -> bool,
+    ) -> Vec<MakaiMessage> {
+        let mut channels = self
+            .channels
+            .read()
+            .await
+            .iter()
+            .filter(|(id, _)| in_channel(id))
+            .map(|(_, it)| it.clone())
+            .collect::<Vec<_>>();
+        channels.extend(
+            self.shared
+                .read()
+                .await
+                .iter()
+                .filter(|(id, _)| id.guild == guild)
+                .map(|(_, it)| it.clone()),
+        );
+
+        let mut messages = Vec::new();
+        for channel in channels {
+            messages.extend(
+                channel
+                    .messages()
+                    .await
+                    .into_iter()
+                    .filter(|it| it.author_id() == Some(user) && it.kind == MessageKind::Message),
+            );
+        }
+
+        messages
+    }
+
     pub async fn user(&self) -> Option<User> {
         self.bot_user.read().await.clone()
     }
 
     pub async fn set_user(&self, user: User) {
-        *self.bot_user.write().await = Some(user);
+        let mut bot_user = self.bot_user.write().await;
+
+        *bot_user = Some(user);
+        if let Some(store) = self.store.get() {
+            store.put_bot_user(&bot_user);
+        }
     }
 }
 
@@ -48,29 +480,259 @@ impl Clone for MakaiContext {
     fn clone(&self) -> Self {
         tokio::task::block_in_place(|| Self {
             channels: RwLock::new(self.channels.blocking_read().clone()),
+            guilds: RwLock::new(self.guilds.blocking_read().clone()),
+            shared: RwLock::new(self.shared.blocking_read().clone()),
+            opted_out_users: RwLock::new(self.opted_out_users.blocking_read().clone()),
+            persona_consent: RwLock::new(self.persona_consent.blocking_read().clone()),
             bot_user: RwLock::new(self.bot_user.blocking_read().clone()),
+            store: OnceLock::new(),
         })
     }
 }
 
 #[derive(Default)]
 pub struct MakaiContextChannel {
-    messages: RwLock<BTreeMap<DateTime<Utc>, MakaiMessage>>,
+    messages: RwLock<MessageLog>,
+    settings: RwLock<ChannelSettings>,
+    /// The guild the channel was last seen in, so its defaults apply when nothing else says
+    guild: RwLock<Option<GuildId>>,
+    /// The channel a thread was started from, unset for other channels
+    parent: RwLock<Option<ChannelId>>,
+    store: OnceLock<ChannelStore>,
 }
 
 impl MakaiContextChannel {
+    fn attach_store(&self, store: Arc<Store>, memory: MemoryId) {
+        let _ = self.store.set(ChannelStore { store, memory });
+    }
+
+    pub async fn settings(&self) -> ChannelSettings {
+        self.settings.read().await.clone()
+    }
+
+    pub async fn update_settings(&self, update: impl FnOnce(&mut ChannelSettings)) {
+        let mut settings = self.settings.write().await;
+
+        update(&mut settings);
+        if let Some(store) = self.store.get() {
+            store.put_settings(&settings);
+        }
+    }
+
+    /// Adds a message, or updates it if it's already remembered
     pub async fn add_message(&self, message: MakaiMessage) {
-        self.messages
-            .write()
-            .await
-            .insert(message.timestamp, message);
+        let mut messages = self.messages.write().await;
+
+        let key = messages.insert(message);
+        if let Some(store) = self.store.get() {
+            store.put_messages(messages.get(&key).map(|it| (&key, it)));
+        }
+    }
+
+    /// Adds several messages at once, updating any that are already remembered
+    pub async fn add_messages(&self, new: impl IntoIterator<Item = MakaiMessage>) {
+        let mut messages = self.messages.write().await;
+
+        let keys = new
+            .into_iter()
+            .map(|it| messages.insert(it))
+            .collect::<Vec<_>>();
+        if let Some(store) = self.store.get() {
+            store.put_messages(
+                keys.iter()
+                    .filter_map(|key| messages.get(key).map(|it| (key, it))),
+            );
+        }
     }
 
     pub async fn clear(&self) {
         self.messages.write().await.clear();
+
+        if let Some(store) = self.store.get() {
+            store.clear();
+        }
+    }
+
+    /// Adds a message, then drops whatever `retention` no longer keeps
+    pub async fn add_message_with_retention(
+        &self,
+        message: MakaiMessage,
+        retention: &RetentionPolicy,
+    ) {
+        self.add_message(message).await;
+        self.prune(retention, Utc::now()).await;
+    }
+
+    /// Drops whatever `retention` no longer keeps at `now`, returning how many were dropped
+    pub async fn prune(&self, retention: &RetentionPolicy, now: DateTime<Utc>) -> usize {
+        let mut messages = self.messages.write().await;
+
+        let expired = retention.expired(&messages, now);
+        for key in &expired {
+            messages.remove(key);
+        }
+
+        self.remove_from_store(expired)
+    }
+
+    pub async fn parent(&self) -> Option<ChannelId> {
+        *self.parent.read().await
+    }
+
+    /// Remembers that the channel is a thread started from `parent`
+    pub async fn set_parent(&self, parent: ChannelId) {
+        if self.parent().await == Some(parent) {
+            return;
+        }
+
+        *self.parent.write().await = Some(parent);
+        if let Some(store) = self.store.get() {
+            store.put_parent(parent);
+        }
+    }
+
+    pub async fn guild(&self) -> Option<GuildId> {
+        *self.guild.read().await
     }
 
-    pub async fn chat_messages(&self, count: usize) -> Vec<ChatMessage> {
+    /// Remembers which guild the channel is in, if it isn't known already
+    async fn set_guild(&self, guild: GuildId) {
+        if self.guild().await == Some(guild) {
+            return;
+        }
+
+        let mut current = self.guild.write().await;
+        if *current == Some(guild) {
+            return;
+        }
+        *current = Some(guild);
+        if let Some(store) = self.store.get() {
+            store.put_guild(guild);
+        }
+    }
+
+    /// Removes every message from the most recent exchange, returning how many were removed
+    pub async fn undo(&self) -> usize {
+        let mut messages = self.messages.write().await;
+
+        let Some(exchange) = messages
+            .values()
+            .rev()
+            .filter(|it| !it.inherited)
+            .find_map(|it| it.exchange)
+        else {
+            return 0;
+        };
+
+        let removed = messages.remove_where(|it| !it.inherited && it.exchange == Some(exchange));
+        self.remove_from_store(removed)
+    }
+
+    /// Removes a remembered message, and reactions to it, by its discord id, returning
+    /// whether it was found
+    pub async fn forget(&self, message_id: MessageId) -> bool {
+        let removed = self.messages.write().await.remove_id(message_id);
+
+        self.remove_from_store(removed) > 0
+    }
+
+    /// Removes messages that were already dropped from memory from the store, returning how
+    /// many there were
+    fn remove_from_store(&self, removed: Vec<MessageKey>) -> usize {
+        let count = removed.len();
+        if count > 0
+            && let Some(store) = self.store.get()
+        {
+            store.remove_messages(removed);
+        }
+
+        count
+    }
+
+    /// Records the discord id of the reply sent for an exchange, so it can be forgotten later
+    pub async fn set_reply_message_id(&self, exchange: u64, message_id: MessageId) {
+        let mut messages = self.messages.write().await;
+
+        let keys = messages
+            .iter()
+            .filter(|(_, it)| it.exchange == Some(exchange))
+            .filter(|(_, it)| matches!(it.sender, MessageSender::MakaiBot))
+            .filter(|(_, it)| it.kind == MessageKind::Message)
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        for key in &keys {
+            messages.set_message_id(key, message_id);
+        }
+
+        if let Some(store) = self.store.get() {
+            store.put_messages(
+                keys.iter()
+                    .filter_map(|key| messages.get(key).map(|it| (key, it))),
+            );
+        }
+    }
+
+    /// Adds to this channel's running token count
+    pub async fn record_usage(&self, usage: &Usage) {
+        if let Some(store) = self.store.get() {
+            store.add_usage(usage);
+        }
+    }
+
+    /// The authors of the last `count` messages, with the names they go by now
+    pub async fn recent_participants(
+        &self,
+        count: usize,
+        names: NameResolver<'_>,
+    ) -> HashMap<UserId, String> {
+        self.messages
+            .read()
+            .await
+            .values()
+            .rev()
+            .take(count)
+            .filter_map(|it| match &it.sender {
+                MessageSender::User(sender) => Some((sender.id?, names.name(sender))),
+                MessageSender::MakaiBot => None,
+            })
+            .collect()
+    }
+
+    /// A snapshot of every remembered message, oldest first
+    pub async fn messages(&self) -> Vec<MakaiMessage> {
+        self.messages.read().await.values().cloned().collect()
+    }
+
+    /// The last `count` messages, oldest first
+    pub async fn recent_messages(&self, count: usize) -> Vec<MakaiMessage> {
+        let messages = self.messages.read().await;
+
+        messages
+            .values()
+            .skip(messages.len().saturating_sub(count))
+            .cloned()
+            .collect()
+    }
+
+    pub async fn is_empty(&self) -> bool {
+        self.messages.read().await.is_empty()
+    }
+
+    /// The content of the bot's last `count` replies, newest first
+    pub async fn recent_replies(&self, count: usize) -> Vec<String> {
+        self.messages
+            .read()
+            .await
+            .values()
+            .rev()
+            .filter(|it| matches!(it.sender, MessageSender::MakaiBot))
+            .filter(|it| it.kind == MessageKind::Message)
+            .take(count)
+            .map(|it| it.content.clone())
+            .collect()
+    }
+
+    pub async fn chat_messages(&self, count: usize, names: NameResolver<'_>) -> Vec<ChatMessage> {
         let mut vec = self
             .messages
             .read()
@@ -81,7 +743,7 @@ impl MakaiContextChannel {
             .take(count)
             .rev()
             // Convert them to chat messages
-            .map(MakaiMessage::to_chat_message)
+            .map(|it| it.to_chat_message(names))
             // Make into a vector
             .collect::<Vec<_>>();
 
@@ -99,6 +761,77 @@ impl Clone for MakaiContextChannel {
     fn clone(&self) -> Self {
         tokio::task::block_in_place(|| Self {
             messages: RwLock::new(self.messages.blocking_read().clone()),
+            settings: RwLock::new(self.settings.blocking_read().clone()),
+            guild: RwLock::new(*self.guild.blocking_read()),
+            parent: RwLock::new(*self.parent.blocking_read()),
+            store: OnceLock::new(),
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct MakaiContextGuild {
+    settings: RwLock<GuildSettings>,
+    words: RwLock<WordList>,
+    facts: RwLock<UserFacts>,
+    store: OnceLock<GuildStore>,
+}
+
+impl MakaiContextGuild {
+    fn attach_store(&self, store: Arc<Store>, guild: GuildId) {
+        let _ = self.store.set(GuildStore { store, guild });
+    }
+
+    /// Writes the whole guild through to the store, it's small enough not to bother with diffs
+    async fn persist(&self) {
+        if let Some(store) = self.store.get() {
+            store.put(serde::MakaiContextGuildSerde {
+                settings: self.settings().await,
+                words: self.words().await,
+                facts: self.facts().await,
+            });
+        }
+    }
+
+    pub async fn settings(&self) -> GuildSettings {
+        self.settings.read().await.clone()
+    }
+
+    pub async fn update_settings(&self, update: impl FnOnce(&mut GuildSettings)) {
+        update(&mut *self.settings.write().await);
+        self.persist().await;
+    }
+
+    pub async fn words(&self) -> WordList {
+        self.words.read().await.clone()
+    }
+
+    pub async fn update_words<R>(&self, update: impl FnOnce(&mut WordList) -> R) -> R {
+        let res = update(&mut *self.words.write().await);
+        self.persist().await;
+
+        res
+    }
+
+    pub async fn facts(&self) -> UserFacts {
+        self.facts.read().await.clone()
+    }
+
+    pub async fn update_facts<R>(&self, update: impl FnOnce(&mut UserFacts) -> R) -> R {
+        let res = update(&mut *self.facts.write().await);
+        self.persist().await;
+
+        res
+    }
+}
+
+impl Clone for MakaiContextGuild {
+    fn clone(&self) -> Self {
+        tokio::task::block_in_place(|| Self {
+            settings: RwLock::new(self.settings.blocking_read().clone()),
+            words: RwLock::new(self.words.blocking_read().clone()),
+            facts: RwLock::new(self.facts.blocking_read().clone()),
+            store: OnceLock::new(),
         })
     }
 }
@@ -108,40 +841,174 @@ pub mod serde {
 
     use super::*;
 
-    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
     pub struct MakaiContextSerde {
-        channels: HashMap<ChannelId, MakaiContextChannelSerde>,
-        bot_user: Option<User>,
+        pub(crate) channels: HashMap<ChannelId, MakaiContextChannelSerde>,
+        #[serde(default)]
+        pub(crate) guilds: HashMap<GuildId, MakaiContextGuildSerde>,
+        #[serde(default)]
+        pub(crate) shared: Vec<SharedMemorySerde>,
+        #[serde(default)]
+        pub(crate) opted_out_users: HashSet<UserId>,
+        #[serde(default)]
+        pub(crate) persona_consent: HashMap<UserId, PersonaConsent>,
+        pub(crate) bot_user: Option<User>,
     }
 
-    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
     pub struct MakaiContextChannelSerde {
-        messages: BTreeMap<DateTime<Utc>, MakaiMessage>,
+        pub(crate) messages: MessageLog,
+        #[serde(default)]
+        pub(crate) settings: ChannelSettings,
+        #[serde(default)]
+        pub(crate) guild: Option<GuildId>,
+        #[serde(default)]
+        pub(crate) parent: Option<ChannelId>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct SharedMemorySerde {
+        pub(crate) guild: GuildId,
+        #[serde(default)]
+        pub(crate) group: Option<String>,
+        pub(crate) messages: MessageLog,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct MakaiContextGuildSerde {
+        #[serde(default)]
+        pub(crate) settings: GuildSettings,
+        #[serde(default)]
+        pub(crate) words: WordList,
+        #[serde(default)]
+        pub(crate) facts: UserFacts,
+    }
+
+    impl From<MakaiContextGuild> for MakaiContextGuildSerde {
+        fn from(value: MakaiContextGuild) -> Self {
+            let MakaiContextGuild {
+                settings,
+                words,
+                facts,
+                ..
+            } = value;
+
+            MakaiContextGuildSerde {
+                settings: settings.into_inner(),
+                words: words.into_inner(),
+                facts: facts.into_inner(),
+            }
+        }
+    }
+
+    impl From<MakaiContextGuildSerde> for MakaiContextGuild {
+        fn from(value: MakaiContextGuildSerde) -> Self {
+            let MakaiContextGuildSerde {
+                settings,
+                words,
+                facts,
+            } = value;
+
+            MakaiContextGuild {
+                settings: settings.into(),
+                words: words.into(),
+                facts: facts.into(),
+                store: OnceLock::new(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ImportMode {
+        /// Adds the imported messages next to the existing ones
+        Merge,
+        /// Replaces the channel's messages with the imported ones
+        Replace,
+    }
+
+    impl MakaiContextChannelSerde {
+        pub fn messages(&self) -> impl Iterator<Item = &MakaiMessage> {
+            self.messages.values()
+        }
+
+        /// Drops the context a thread inherited from its parent, which isn't the thread's own
+        pub fn without_inherited(mut self) -> Self {
+            self.messages.remove_where(|it| it.inherited);
+            self
+        }
+    }
+
+    impl MakaiContextChannel {
+        pub async fn import(&self, other: MakaiContextChannelSerde, mode: ImportMode) {
+            // Only messages are imported, anyone can upload a file but settings need `/config`
+            // and its permission checks and validation. The export may also come from another
+            // guild, so the channel keeps its own
+            let MakaiContextChannelSerde { messages, .. } = other;
+
+            match mode {
+                ImportMode::Merge => self.add_messages(messages.values().cloned()).await,
+                ImportMode::Replace => {
+                    let mut current = self.messages.write().await;
+
+                    if let Some(store) = self.store.get() {
+                        store.clear();
+                        store.put_messages(messages.iter());
+                    }
+                    *current = messages;
+                }
+            }
+        }
     }
 
     impl From<MakaiContextChannel> for MakaiContextChannelSerde {
         fn from(value: MakaiContextChannel) -> Self {
-            let MakaiContextChannel { messages } = value;
+            let MakaiContextChannel {
+                messages,
+                settings,
+                guild,
+                parent,
+                ..
+            } = value;
 
             MakaiContextChannelSerde {
                 messages: messages.into_inner(),
+                settings: settings.into_inner(),
+                guild: guild.into_inner(),
+                parent: parent.into_inner(),
             }
         }
     }
 
     impl From<MakaiContextChannelSerde> for MakaiContextChannel {
         fn from(value: MakaiContextChannelSerde) -> Self {
-            let MakaiContextChannelSerde { messages } = value;
+            let MakaiContextChannelSerde {
+                messages,
+                settings,
+                guild,
+                parent,
+            } = value;
 
             MakaiContextChannel {
                 messages: messages.into(),
+                settings: settings.into(),
+                guild: guild.into(),
+                parent: parent.into(),
+                store: OnceLock::new(),
             }
         }
     }
 
     impl From<MakaiContext> for MakaiContextSerde {
         fn from(value: MakaiContext) -> Self {
-            let MakaiContext { channels, bot_user } = value;
+            let MakaiContext {
+                channels,
+                guilds,
+                shared,
+                opted_out_users,
+                persona_consent,
+                bot_user,
+                ..
+            } = value;
 
             MakaiContextSerde {
                 channels: channels
@@ -149,6 +1016,22 @@ pub mod serde {
                     .into_iter()
                     .map(|(channel, ctx)| (channel, Arc::unwrap_or_clone(ctx).into()))
                     .collect(),
+                guilds: guilds
+                    .into_inner()
+                    .into_iter()
+                    .map(|(guild, ctx)| (guild, Arc::unwrap_or_clone(ctx).into()))
+                    .collect(),
+                shared: shared
+                    .into_inner()
+                    .into_iter()
+                    .map(|(SharedMemoryId { guild, group }, ctx)| SharedMemorySerde {
+                        guild,
+                        group,
+                        messages: Arc::unwrap_or_clone(ctx).messages.into_inner(),
+                    })
+                    .collect(),
+                opted_out_users: opted_out_users.into_inner(),
+                persona_consent: persona_consent.into_inner(),
                 bot_user: bot_user.into_inner(),
             }
         }
@@ -156,7 +1039,14 @@ pub mod serde {
 
     impl From<MakaiContextSerde> for MakaiContext {
         fn from(value: MakaiContextSerde) -> Self {
-            let MakaiContextSerde { channels, bot_user } = value;
+            let MakaiContextSerde {
+                channels,
+                guilds,
+                shared,
+                opted_out_users,
+                persona_consent,
+                bot_user,
+            } = value;
 
             MakaiContext {
                 channels: channels
@@ -164,7 +1054,32 @@ pub mod serde {
                     .map(|(channel, ctx)| (channel, Arc::new(ctx.into())))
                     .collect::<HashMap<_, _>>()
                     .into(),
+                guilds: guilds
+                    .into_iter()
+                    .map(|(guild, ctx)| (guild, Arc::new(ctx.into())))
+                    .collect::<HashMap<_, _>>()
+                    .into(),
+                shared: shared
+                    .into_iter()
+                    .map(
+                        |SharedMemorySerde {
+                             guild,
+                             group,
+                             messages,
+                         }| {
+                            let ctx = MakaiContextChannel {
+                                messages: messages.into(),
+                                ..Default::default()
+                            };
+                            (SharedMemoryId { guild, group }, Arc::new(ctx))
+                        },
+                    )
+                    .collect::<HashMap<_, _>>()
+                    .into(),
+                opted_out_users: opted_out_users.into(),
+                persona_consent: persona_consent.into(),
                 bot_user: bot_user.into(),
+                store: OnceLock::new(),
             }
         }
     }
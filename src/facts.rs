@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::env;
+
+use anyhow::{Context as _, bail};
+use chrono::{DateTime, Utc};
+use llm::chat::{ChatMessage, StructuredOutputFormat};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use serenity::all::UserId;
+
+use crate::ai::{self, MakaiMessage, MessageKind, MessageSender, NameResolver};
+use crate::settings::GenerationSettings;
+use crate::words::same_word;
+
+/// Longer entries are stories, not facts
+pub const MAX_FACT_LEN: usize = 100;
+/// Every fact about a participant ends up in the prompt, so a user can't have too many
+pub const MAX_USER_FACTS: usize = 15;
+/// Keeps the extraction prompt within a reasonable size
+const MAX_EXTRACTION_CHARS: usize = 20_000;
+/// Proposals are listed in one message, which discord limits to 2000 characters
+const MAX_EXTRACTED_FACTS: usize = 10;
+
+/// Something makai knows about a member, eg. a nickname or a running gag
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fact {
+    pub text: String,
+    pub added_at: DateTime<Utc>,
+    /// Who added it, unset for facts proposed by `/fact extract`
+    #[serde(default)]
+    pub added_by: Option<UserId>,
+    /// Waiting for the user or an admin to approve it, pending facts aren't used
+    #[serde(default)]
+    pub pending: bool,
+}
+
+/// A guild's facts about its members
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserFacts {
+    facts: HashMap<UserId, Vec<Fact>>,
+}
+
+impl UserFacts {
+    /// Everything known about a user, including pending facts, oldest first
+    pub fn facts(&self, user: &UserId) -> &[Fact] {
+        self.facts.get(user).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Adds a fact about `user`, or proposes it if `pending`. Returns false if it's already
+    /// known
+    pub fn add(
+        &mut self,
+        user: UserId,
+        text: &str,
+        added_by: Option<UserId>,
+        pending: bool,
+    ) -> anyhow::Result<bool> {
+        let text = normalize(text)?;
+        let facts = self.facts(&user);
+        if facts.iter().any(|it| same_word(&it.text, &text)) {
+            return Ok(false);
+        }
+        if facts.len() >= MAX_USER_FACTS {
+            bail!("That user already has {MAX_USER_FACTS} facts");
+        }
+
+        self.facts.entry(user).or_default().push(Fact {
+            text,
+            added_at: Utc::now(),
+            added_by,
+            pending,
+        });
+
+        Ok(true)
+    }
+
+    /// Removes a fact or proposal, returning it if it was known
+    pub fn remove(&mut self, user: &UserId, text: &str) -> Option<Fact> {
+        let facts = self.facts.get_mut(user)?;
+        let idx = facts.iter().position(|it| same_word(&it.text, text))?;
+
+        let fact = facts.remove(idx);
+        if facts.is_empty() {
+            self.facts.remove(user);
+        }
+
+        Some(fact)
+    }
+
+    /// Starts using a proposed fact, returns whether it was pending
+    pub fn approve(&mut self, user: &UserId, text: &str) -> bool {
+        let fact = self
+            .facts
+            .get_mut(user)
+            .and_then(|it| it.iter_mut().find(|it| same_word(&it.text, text)))
+            .filter(|it| it.pending);
+
+        match fact {
+            Some(fact) => {
+                fact.pending = false;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The approved facts about `participants` for the prompt, none if nobody has any
+    pub fn render(&self, participants: &HashMap<UserId, String>) -> Option<String> {
+        let mut participants = participants.iter().collect::<Vec<_>>();
+        participants.sort_by_key(|(id, name)| (*name, *id));
+
+        let facts = participants
+            .into_iter()
+            .flat_map(|(id, name)| {
+                self.facts(id)
+                    .iter()
+                    .filter(|it| !it.pending)
+                    .map(move |it| format!("- `{name}`: {}\n", it.text))
+            })
+            .collect::<String>();
+
+        if facts.is_empty() {
+            None
+        } else {
+            Some(format!(
+                "Things you know about the people in this conversation:\n{facts}"
+            ))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ExtractedFacts {
+    facts: Vec<ExtractedFact>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExtractedFact {
+    user: String,
+    fact: String,
+}
+
+/// Asks the llm for durable facts about the authors of `messages`, returning the ones about
+/// users it could attribute them to
+pub async fn extract_facts(
+    settings: &GenerationSettings,
+    names: NameResolver<'_>,
+    messages: &[MakaiMessage],
+) -> anyhow::Result<Vec<(UserId, String)>> {
+    let prompt_file = env::var("LLM_FACT_PROMPT_FILE")
+        .unwrap_or_else(|_| "./fact_extraction_prompt.txt".to_string());
+    let system = tokio::fs::read_to_string(prompt_file)
+        .await
+        .context("Read fact extraction prompt file")?;
+
+    let mut authors = HashMap::new();
+    let mut transcript = String::new();
+    for message in messages.iter().rev() {
+        let (MessageSender::User(sender), Some(id), MessageKind::Message) =
+            (&message.sender, message.author_id(), message.kind)
+        else {
+            continue;
+        };
+        let name = names.name(sender);
+        let line = format!("`{name}`: {}\n", message.content.replace('\n', " "));
+        if transcript.len() + line.len() > MAX_EXTRACTION_CHARS {
+            break;
+        }
+
+        // Newest first so the most recent messages survive the size limit. Facts about a name
+        // several authors share can't be attributed, so they're dropped
+        authors
+            .entry(name)
+            .and_modify(|it: &mut Option<UserId>| {
+                if *it != Some(id) {
+                    *it = None;
+                }
+            })
+            .or_insert(Some(id));
+        transcript.insert_str(0, &line);
+    }
+    if transcript.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let schema = StructuredOutputFormat {
+        name: "facts".to_string(),
+        description: Some("Durable facts about the people in a conversation".to_string()),
+        schema: Some(json!({
+            "type": "object",
+            "properties": {
+                "facts": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "user": { "type": "string" },
+                            "fact": { "type": "string" },
+                        },
+                        "required": ["user", "fact"],
+                        "additionalProperties": false,
+                    },
+                    "maxItems": MAX_EXTRACTED_FACTS,
+                },
+            },
+            "required": ["facts"],
+            "additionalProperties": false,
+        })),
+        strict: Some(true),
+    };
+    let settings = GenerationSettings {
+        max_tokens: None,
+        ..settings.clone()
+    };
+    let llm = ai::llm_builder(&settings, system)?
+        .schema(schema)
+        .build()
+        .context("Failed to build LLM")?;
+
+    let response = llm
+        .chat(&[ChatMessage::user().content(transcript).build()])
+        .await
+        .context("LLM Error")?;
+
+    let text = response.text().unwrap_or_default();
+    let text = text
+        .trim()
+        .trim_start_matches("```json")
+        .trim_matches('`')
+        .trim();
+    let extracted: ExtractedFacts = serde_json::from_str(text).context("Parse facts")?;
+
+    Ok(extracted
+        .facts
+        .into_iter()
+        .take(MAX_EXTRACTED_FACTS)
+        .filter_map(|it| {
+            let user = it.user.trim().trim_matches('`');
+            let id = authors
+                .iter()
+                .find(|(name, _)| same_word(name, user))
+                .and_then(|(_, id)| *id)?;
+            Some((id, it.fact))
+        })
+        .collect())
+}
+
+fn normalize(text: &str) -> anyhow::Result<String> {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    if text.is_empty() {
+        bail!("Facts can't be empty");
+    }
+    if text.chars().count() > MAX_FACT_LEN {
+        bail!("Facts can be at most {MAX_FACT_LEN} characters long");
+    }
+
+    Ok(text)
+}
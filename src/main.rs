@@ -2,52 +2,142 @@
 // TODO: Show thoughts option
 
 pub mod ai;
+pub mod auto_reply;
+pub mod backup;
+pub mod chime_in;
 pub mod commands;
 pub mod context;
+pub mod facts;
+pub mod mentions;
+pub mod message_log;
+pub mod moderation;
+pub mod passive;
+pub mod personas;
+pub mod reactions;
+pub mod retention;
+pub mod schema;
+pub mod scoring;
+pub mod settings;
+pub mod store;
+pub mod threads;
 pub mod utils;
+pub mod words;
 
 use std::env;
 use std::sync::Arc;
 use std::time::Duration;
 
-use anyhow::Context as _;
-use futures::FutureExt;
-use serenity::all::ShardManager;
+use anyhow::{Context as _, bail};
+use chrono::Utc;
+use serenity::all::{GuildChannel, ShardManager};
 use tracing::level_filters::LevelFilter;
 use tracing::{debug, error, info, warn};
 use tracing_subscriber::{EnvFilter, Layer, layer::SubscriberExt};
 
 use serenity::async_trait;
 use serenity::model::application::Interaction;
+use serenity::model::channel::Message;
 use serenity::model::gateway::Ready;
 use serenity::prelude::*;
 
+use crate::chime_in::ChimeInScheduler;
 use crate::commands::MakaiCommandRegistry;
 use crate::context::MakaiContext;
-use crate::context::serde::MakaiContextSerde;
+use crate::store::Store;
 
+/// The old state file, migrated into the database on first start
 const STATE_PATH: &str = "./makai_state.json";
-const SAVE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const DB_PATH: &str = "./makai_state.redb";
+const BACKUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const RETENTION_SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
 
 struct Handler {
     commands: MakaiCommandRegistry<'static>,
     context: MakaiContext,
+    chime_in: ChimeInScheduler,
 }
 
 #[async_trait]
 impl EventHandler for Handler {
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
-        if let Interaction::Command(command) = interaction {
-            debug!("Received command interaction: {command:#?}");
+        match interaction {
+            Interaction::Command(command) => {
+                debug!("Received command interaction: {command:#?}");
+                threads::track_command(&self.context, &command).await;
+
+                let res = self
+                    .commands
+                    .handle_command(&self.context, ctx, &command)
+                    .await;
+
+                if let Err(err) = res {
+                    error!("Error while handeling command: {err:?}");
+                }
+            }
+            Interaction::Autocomplete(command) => {
+                debug!("Received autocomplete interaction: {command:#?}");
+
+                let res = self
+                    .commands
+                    .handle_autocomplete(&self.context, ctx, &command)
+                    .await;
+
+                if let Err(err) = res {
+                    error!("Error while handeling autocomplete: {err:?}");
+                }
+            }
+            Interaction::Component(component) => {
+                debug!("Received component interaction: {component:#?}");
+
+                let res = self
+                    .commands
+                    .handle_component(&self.context, ctx, &component)
+                    .await;
+
+                if let Err(err) = res {
+                    error!("Error while handeling component: {err:?}");
+                }
+            }
+            Interaction::Modal(modal) => {
+                debug!("Received modal interaction: {modal:#?}");
 
-            let res = self
-                .commands
-                .handle_command(&self.context, ctx, &command)
-                .await;
+                let res = self.commands.handle_modal(&self.context, ctx, &modal).await;
 
-            if let Err(err) = res {
-                error!("Error while handeling command: {err:?}");
+                if let Err(err) = res {
+                    error!("Error while handeling modal: {err:?}");
+                }
+            }
+            _ => {}
+        }
+    }
+
+    async fn thread_create(&self, _ctx: Context, thread: GuildChannel) {
+        threads::handle_thread_create(&self.context, &thread).await;
+    }
+
+    async fn message(&self, ctx: Context, message: Message) {
+        threads::track_message(&self.context, &ctx, &message).await;
+
+        match auto_reply::handle_message(&self.context, &ctx, &message).await {
+            // Replying already stored the message
+            Ok(true) => {}
+            Ok(false) => {
+                match chime_in::handle_message(&self.chime_in, &self.context, &ctx, &message).await
+                {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        passive::record_message(&self.context, &ctx, &message).await;
+
+                        if let Err(err) =
+                            reactions::maybe_react(&self.context, &ctx, &message).await
+                        {
+                            error!("Error while reacting: {err:?}");
+                        }
+                    }
+                    Err(err) => error!("Error while chiming in: {err:?}"),
+                }
             }
+            Err(err) => error!("Error while auto replying: {err:?}"),
         }
     }
 
@@ -78,22 +168,44 @@ async fn main() -> anyhow::Result<()> {
     );
     tracing::subscriber::set_global_default(subscriber)?;
 
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [] => {}
+        ["state", "prune"] => return prune_state().await,
+        _ => bail!("Unknown arguments, expected none or `state prune`"),
+    }
+
     // Configure the client with your Discord bot token in the environment.
     let token = env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
 
+    let (context, store) = load_state().await.context("Load State")?;
+    start_backups(store.clone());
+
     let handler = Handler {
         commands: MakaiCommandRegistry::default(),
-        context: load_state().await.context("Load State")?,
+        context,
+        chime_in: ChimeInScheduler::from_env(),
     };
     let handler = Arc::new(handler);
+    start_retention_sweeper(handler.clone());
 
     // Build our client.
-    let mut client = Client::builder(token, GatewayIntents::empty())
+    // Message content is a privileged intent, it has to be enabled in the developer portal
+    let intents = GatewayIntents::GUILDS
+        | GatewayIntents::GUILD_MESSAGES
+        | GatewayIntents::DIRECT_MESSAGES
+        | GatewayIntents::MESSAGE_CONTENT;
+    let mut client = Client::builder(token, intents)
         .event_handler_arc(handler.clone())
         .await
         .expect("Error creating client");
 
-    start_auto_save_and_shutdown_hooks(handler.clone(), client.shard_manager.clone());
+    start_shutdown_hooks(client.shard_manager.clone());
 
     // Finally, start a single shard, and start listening to events.
     //
@@ -103,14 +215,73 @@ async fn main() -> anyhow::Result<()> {
         error!("Client error: {why:?}");
     }
 
-    save_state(handler.context.clone())
-        .await
-        .context("Save state")?;
+    // Changes are committed in the background, make sure the last ones land
+    tokio::task::block_in_place(|| store.flush());
 
     Ok(())
 }
 
-async fn load_state() -> anyhow::Result<MakaiContext> {
+async fn load_state() -> anyhow::Result<(MakaiContext, Arc<Store>)> {
+    let store = match open_store().await {
+        Ok(store) => store,
+        Err(err) if store::is_corrupted(&err) => {
+            error!("!!! {DB_PATH} is corrupted, falling back to the newest backup: {err:?}");
+            restore_backup().await?
+        }
+        // Anything else, eg. another instance holding the database, a newer schema version
+        // or I/O errors, would just lose the state if the database was moved aside
+        Err(err) => return Err(err),
+    };
+
+    let context: MakaiContext = store.load().context("Load database")?.into();
+    let store = Arc::new(store);
+    context.attach_store(store.clone()).await;
+
+    Ok((context, store))
+}
+
+async fn open_store() -> anyhow::Result<Store> {
+    let store = Store::open(DB_PATH).context("Open database")?;
+
+    if store.is_empty().context("Check if database is empty")? {
+        migrate_json_state(&store).await?;
+    }
+    store.migrate().context("Migrate database")?;
+
+    // Make sure everything can be read before committing to this database
+    store.load().context("Load database")?;
+
+    Ok(store)
+}
+
+/// Moves a broken database aside and starts a new one from the newest valid backup
+async fn restore_backup() -> anyhow::Result<Store> {
+    if tokio::fs::try_exists(DB_PATH).await.unwrap_or(false) {
+        let broken = format!("{DB_PATH}.broken-{}", Utc::now().format("%Y%m%dT%H%M%SZ"));
+        tokio::fs::rename(DB_PATH, &broken)
+            .await
+            .context("Move broken database aside")?;
+        warn!("!!! Moved the broken database to {broken}");
+    }
+
+    let store = Store::open(DB_PATH).context("Open database")?;
+    match backup::newest_valid_backup().await? {
+        Some((path, state)) => {
+            store.import(&state).context("Import backup")?;
+            warn!(
+                "!!! Restored state from backup {}, changes since then are lost",
+                path.display()
+            );
+        }
+        None => error!("!!! No valid backup found, starting with clean state!"),
+    }
+
+    Ok(store)
+}
+
+/// Moves state from the old json file into the database, then renames the file so it's only
+/// migrated once
+async fn migrate_json_state(store: &Store) -> anyhow::Result<()> {
     let exists = tokio::fs::try_exists(&STATE_PATH)
         .await
         .context("Check if state exists")?;
@@ -118,41 +289,86 @@ async fn load_state() -> anyhow::Result<MakaiContext> {
     if !exists {
         info!("No saved state found, Starting with clean state!");
 
-        return Ok(MakaiContext::default());
+        return Ok(());
     }
 
-    let state = tokio::fs::read_to_string(&STATE_PATH)
+    info!("Migrating {STATE_PATH} to {DB_PATH}");
+
+    let state = tokio::fs::read(&STATE_PATH).await.context("Read State")?;
+    let state = schema::decode_state(&state).context("Parse State")?;
+    store.import(&state).context("Import State")?;
+
+    tokio::fs::rename(&STATE_PATH, format!("{STATE_PATH}.migrated"))
         .await
-        .context("Read State")?;
-    let state: MakaiContextSerde = serde_json::from_str(&state).context("Parse State")?;
+        .context("Rename migrated state")?;
 
-    Ok(state.into())
+    Ok(())
 }
 
-async fn save_state(state: MakaiContext) -> anyhow::Result<()> {
-    info!("Saving");
-
-    let state: MakaiContextSerde = state.into();
-    let state = serde_json::to_vec(&state).context("Encode State")?;
-    tokio::fs::write(&STATE_PATH, state)
+/// `makai state prune`, applies every channel's retention policy once, then shrinks the
+/// database file
+async fn prune_state() -> anyhow::Result<()> {
+    let (context, store) = load_state()
         .await
-        .context("Write State")?;
+        .context("Load State, is the bot still running?")?;
+
+    let dropped = context.prune(Utc::now()).await;
+    info!("Pruned {dropped} messages");
+
+    // Channels hold on to the store, so the context has to go before it can be compacted
+    drop(context);
+    let mut store = Arc::into_inner(store).context("Database is still in use")?;
+    tokio::task::block_in_place(|| store.compact()).context("Compact database")?;
+    info!("Compacted {DB_PATH}");
 
     Ok(())
 }
 
-fn start_auto_save_and_shutdown_hooks(state: Arc<Handler>, shard_manager: Arc<ShardManager>) {
-    let (tx_needs_save, rx_needs_save) = tokio::sync::oneshot::channel::<()>();
-    let (tx_save_done, rx_save_done) = tokio::sync::oneshot::channel::<()>();
+/// Periodically drops messages that fell out of their channel's retention policy
+fn start_retention_sweeper(handler: Arc<Handler>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RETENTION_SWEEP_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let dropped = handler.context.prune(Utc::now()).await;
+            if dropped > 0 {
+                info!("Retention sweep dropped {dropped} messages");
+            }
+        }
+    });
+}
+
+/// Periodically snapshots the database into rotating backups
+fn start_backups(store: Arc<Store>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(BACKUP_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(err) = backup::write_backup(&store).await {
+                error!("Error while backing up: {err:?}");
+            }
+        }
+    });
+}
+
+/// Every change is queued for the database as it happens, so shutting down only has to stop
+/// the shards before `main` waits for the last commit
+fn start_shutdown_hooks(shard_manager: Arc<ShardManager>) {
+    let (tx_shutdown, rx_shutdown) = tokio::sync::oneshot::channel::<()>();
+    let (tx_shutdown_done, rx_shutdown_done) = tokio::sync::oneshot::channel::<()>();
 
     {
-        let mut channels = Some((tx_needs_save, rx_save_done));
+        let mut channels = Some((tx_shutdown, rx_shutdown_done));
 
         ctrlc::set_handler(move || {
-            if let Some((tx_needs_save, rx_save_done)) = channels.take() {
+            if let Some((tx_shutdown, rx_shutdown_done)) = channels.take() {
                 info!("Got ctrlc");
-                tx_needs_save.send(()).unwrap();
-                let _ = rx_save_done.blocking_recv();
+                tx_shutdown.send(()).unwrap();
+                let _ = rx_shutdown_done.blocking_recv();
                 info!("Exiting");
             } else {
                 warn!("Got multiple ctrlc");
@@ -161,34 +377,12 @@ fn start_auto_save_and_shutdown_hooks(state: Arc<Handler>, shard_manager: Arc<Sh
         .expect("Set term handler");
     }
 
-    let mut tx_save_done = Some(tx_save_done);
-    let mut rx_needs_save = rx_needs_save.fuse();
-
     tokio::spawn(async move {
-        let mut interval = tokio::time::interval(SAVE_INTERVAL);
+        if rx_shutdown.await.is_ok() {
+            info!("Shutting Down");
+            shard_manager.shutdown_all().await;
 
-        loop {
-            tokio::select! {
-                _ = interval.tick() => {
-                    let res = save_state(state.context.clone()).await;
-                    if let Err(err) = res {
-                        error!("Error while saving: {err:?}");
-                    }
-                }
-                _ = &mut rx_needs_save => {
-                    let res = save_state(state.context.clone()).await;
-                    if let Err(err) = res {
-                        error!("Error while saving: {err:?}");
-                    }
-
-                    if let Some(tx_save_done) = tx_save_done.take() {
-                        info!("Shutting Down");
-                        shard_manager.shutdown_all().await;
-
-                        let _ = tx_save_done.send(());
-                    }
-                }
-            }
+            let _ = tx_shutdown_done.send(());
         }
     });
 }
@@ -0,0 +1,312 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+
+use regex::{Captures, Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serenity::all::{
+    Cache, ChannelId, ChannelMention, CreateAllowedMentions, GuildId, RoleId, User, UserId,
+};
+
+use crate::utils::user_to_name;
+
+/// The most users a message's allowed mentions can list
+const MAX_ALLOWED_USERS: usize = 100;
+
+static MENTION_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"<(?:(?P<user>@!?)|(?P<role>@&)|(?P<channel>#)|a?:(?P<emoji>\w+):)(?P<id>\d+)>")
+        .expect("Valid mention regex")
+});
+
+static MASS_MENTION_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    RegexBuilder::new(r"@(everyone|here)")
+        .case_insensitive(true)
+        .build()
+        .expect("Valid mass mention regex")
+});
+
+/// Everything we know about the mentions in a message, used to turn discord's raw
+/// `<@id>` style tokens into something the model can make sense of
+pub struct MentionSource<'a> {
+    pub cache: &'a Cache,
+    pub guild_id: Option<GuildId>,
+    pub users: &'a [User],
+    pub channels: &'a [ChannelMention],
+}
+
+impl MentionSource<'_> {
+    /// Rewrites user, role, channel and custom emoji mentions into `@DisplayName`,
+    /// `@Role`, `#channel` and `:emoji:`
+    pub fn resolve(&self, content: &str) -> String {
+        MENTION_REGEX
+            .replace_all(content, |caps: &Captures| {
+                let Ok(id) = caps["id"].parse::<u64>() else {
+                    return caps[0].to_string();
+                };
+
+                if caps.name("user").is_some() {
+                    format!("@{}", self.user_name(UserId::new(id)))
+                } else if caps.name("role").is_some() {
+                    format!("@{}", self.role_name(RoleId::new(id)))
+                } else if caps.name("channel").is_some() {
+                    format!("#{}", self.channel_name(ChannelId::new(id)))
+                } else if let Some(emoji) = caps.name("emoji") {
+                    format!(":{}:", emoji.as_str())
+                } else {
+                    caps[0].to_string()
+                }
+            })
+            .into_owned()
+    }
+
+    fn user_name(&self, id: UserId) -> String {
+        if let Some(user) = self.users.iter().find(|it| it.id == id) {
+            if let Some(nick) = user.member.as_ref().and_then(|it| it.nick.as_ref()) {
+                return nick.clone();
+            }
+
+            return user_to_name(user).to_string();
+        }
+
+        if let Some(member) = self
+            .guild_id
+            .and_then(|guild| self.cache.guild(guild))
+            .and_then(|guild| {
+                guild
+                    .members
+                    .get(&id)
+                    .map(|it| it.display_name().to_string())
+            })
+        {
+            return member;
+        }
+
+        self.cache
+            .user(id)
+            .map(|it| user_to_name(&it).to_string())
+            .unwrap_or_else(|| "unknown-user".to_string())
+    }
+
+    fn role_name(&self, id: RoleId) -> String {
+        self.guild_id
+            .and_then(|guild| self.cache.guild(guild))
+            .and_then(|guild| guild.roles.get(&id).map(|it| it.name.clone()))
+            .unwrap_or_else(|| "unknown-role".to_string())
+    }
+
+    fn channel_name(&self, id: ChannelId) -> String {
+        if let Some(channel) = self.channels.iter().find(|it| it.id == id) {
+            return channel.name.clone();
+        }
+
+        self.guild_id
+            .and_then(|guild| self.cache.guild(guild))
+            .and_then(|guild| {
+                guild
+                    .channels
+                    .get(&id)
+                    .or_else(|| guild.threads.iter().find(|it| it.id == id))
+                    .map(|it| it.name.clone())
+            })
+            .unwrap_or_else(|| "unknown-channel".to_string())
+    }
+}
+
+/// Turns `@Name` in model output back into real mentions, but only for the given
+/// participants of the current conversation. Names shared by several participants are left
+/// alone, there's no telling who was meant
+pub fn restore_mentions(content: &str, participants: &HashMap<UserId, String>) -> String {
+    let mut lookup = HashMap::<String, Option<UserId>>::new();
+    for (id, name) in participants {
+        lookup
+            .entry(name.to_lowercase())
+            .and_modify(|it| *it = None)
+            .or_insert(Some(*id));
+    }
+    let lookup = lookup
+        .into_iter()
+        .filter_map(|(name, id)| Some((name, id?)))
+        .collect::<HashMap<_, _>>();
+    if lookup.is_empty() {
+        return content.to_string();
+    }
+
+    // Longest names first so `@Bob Jr` wins over `@Bob`
+    let mut names = lookup.keys().collect::<Vec<_>>();
+    names.sort_by_key(|it| std::cmp::Reverse(it.len()));
+
+    let pattern = names
+        .iter()
+        .map(|it| regex::escape(it))
+        .collect::<Vec<_>>()
+        .join("|");
+    let Ok(regex) = RegexBuilder::new(&format!("@({pattern})"))
+        .case_insensitive(true)
+        .build()
+    else {
+        return content.to_string();
+    };
+
+    regex
+        .replace_all(content, |caps: &Captures| {
+            // `@Bobby` isn't `@Bob`, the name has to end where the word does
+            let rest = &content[caps.get(0).map_or(0, |it| it.end())..];
+            if rest.starts_with(|it: char| it.is_alphanumeric() || it == '_') {
+                return caps[0].to_string();
+            }
+
+            match lookup.get(&caps[1].to_lowercase()) {
+                Some(id) => format!("<@{id}>"),
+                None => caps[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// Who the bot is allowed to ping when it posts a response
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MentionPolicy {
+    /// Never ping anyone
+    Nobody,
+    /// Only ping the user who triggered the response
+    InvokingUser,
+    /// Ping any user who took part in the current conversation
+    #[default]
+    Participants,
+}
+
+impl MentionPolicy {
+    pub const ALL: [MentionPolicy; 3] = [
+        MentionPolicy::Nobody,
+        MentionPolicy::InvokingUser,
+        MentionPolicy::Participants,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            MentionPolicy::Nobody => "nobody",
+            MentionPolicy::InvokingUser => "invoking_user",
+            MentionPolicy::Participants => "participants",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            MentionPolicy::Nobody => "Never ping anyone",
+            MentionPolicy::InvokingUser => "Only ping the user who used the command",
+            MentionPolicy::Participants => "Ping users from the current conversation",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|it| it.id() == id)
+    }
+
+    /// Builds the allowed mentions for a response. Roles, `@everyone` and `@here` are
+    /// never allowed regardless of policy
+    pub fn allowed_mentions(
+        &self,
+        invoker: UserId,
+        participants: &HashMap<UserId, String>,
+    ) -> CreateAllowedMentions {
+        let allowed = CreateAllowedMentions::new()
+            .everyone(false)
+            .all_roles(false)
+            .all_users(false)
+            .replied_user(false);
+
+        match self {
+            MentionPolicy::Nobody => allowed.empty_users(),
+            MentionPolicy::InvokingUser => allowed.users([invoker]),
+            MentionPolicy::Participants => {
+                let mut seen = HashSet::new();
+                let users = [invoker]
+                    .into_iter()
+                    .chain(participants.keys().copied())
+                    .filter(|it| seen.insert(*it))
+                    // Discord rejects the whole message if more users than that are listed
+                    .take(MAX_ALLOWED_USERS)
+                    .collect::<Vec<_>>();
+                allowed.users(users)
+            }
+        }
+    }
+}
+
+/// Defuses mass pings in text by breaking up `@everyone` and `@here` with a zero width
+/// space, so they don't even look like pings to the reader
+pub fn sanitize_mass_mentions(content: &str) -> String {
+    MASS_MENTION_REGEX
+        .replace_all(content, "@\u{200B}$1")
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(id: u64, name: &str) -> User {
+        let mut user = User::default();
+        user.id = UserId::new(id);
+        user.name = name.to_string();
+        user
+    }
+
+    fn participants(names: &[(u64, &str)]) -> HashMap<UserId, String> {
+        names
+            .iter()
+            .map(|(id, name)| (UserId::new(*id), name.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn resolves_mentions() {
+        let cache = Cache::new();
+        let users = [user(1, "alice")];
+        let source = MentionSource {
+            cache: &cache,
+            guild_id: None,
+            users: &users,
+            channels: &[],
+        };
+
+        assert_eq!(
+            source.resolve("<@1> <@!1> <@2> <@&3> <#4> <:wave:5> <a:spin:6>"),
+            "@alice @alice @unknown-user @unknown-role #unknown-channel :wave: :spin:"
+        );
+    }
+
+    #[test]
+    fn restores_participant_mentions() {
+        let participants = participants(&[(1, "Bob"), (2, "Bob Jr")]);
+
+        assert_eq!(
+            restore_mentions("@bob and @Bob Jr, not @Carol", &participants),
+            "<@1> and <@2>, not @Carol"
+        );
+    }
+
+    #[test]
+    fn restore_stops_at_word_boundaries() {
+        let participants = participants(&[(1, "Bob")]);
+
+        assert_eq!(
+            restore_mentions("@Bobby @bob_ @Bob!", &participants),
+            "@Bobby @bob_ <@1>!"
+        );
+    }
+
+    #[test]
+    fn restore_skips_shared_names() {
+        let participants = participants(&[(1, "Sam"), (2, "sam"), (3, "Alex")]);
+
+        assert_eq!(restore_mentions("@Sam @Alex", &participants), "@Sam <@3>");
+    }
+
+    #[test]
+    fn sanitizes_mass_mentions() {
+        assert_eq!(
+            sanitize_mass_mentions("@everyone @HERE @someone"),
+            "@\u{200B}everyone @\u{200B}HERE @someone"
+        );
+    }
+}
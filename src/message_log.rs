@@ -0,0 +1,295 @@
+use std::collections::{BTreeMap, BTreeSet, btree_map};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serenity::all::MessageId;
+
+use crate::ai::{MakaiMessage, MessageKind};
+
+/// Orders a channel's messages by time, with a sequence number so messages from the same
+/// instant don't overwrite each other
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MessageKey {
+    pub timestamp: DateTime<Utc>,
+    pub seq: u64,
+}
+
+/// How a message and its key are serialized
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredMessage {
+    pub seq: u64,
+    #[serde(flatten)]
+    pub message: MakaiMessage,
+}
+
+/// A channel's messages, oldest first, indexed by their discord ids
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "Vec<StoredMessage>", into = "Vec<StoredMessage>")]
+pub struct MessageLog {
+    messages: BTreeMap<MessageKey, MakaiMessage>,
+    /// Every entry referring to a discord message, ie. the message itself and reactions to it
+    by_id: BTreeMap<MessageId, BTreeSet<MessageKey>>,
+    next_seq: u64,
+}
+
+impl MessageLog {
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    pub fn get(&self, key: &MessageKey) -> Option<&MakaiMessage> {
+        self.messages.get(key)
+    }
+
+    pub fn iter(&self) -> btree_map::Iter<'_, MessageKey, MakaiMessage> {
+        self.messages.iter()
+    }
+
+    pub fn keys(&self) -> btree_map::Keys<'_, MessageKey, MakaiMessage> {
+        self.messages.keys()
+    }
+
+    pub fn values(&self) -> btree_map::Values<'_, MessageKey, MakaiMessage> {
+        self.messages.values()
+    }
+
+    /// Adds a message, returning its key. A message that's already remembered is updated in
+    /// place instead of being added twice
+    pub fn insert(&mut self, message: MakaiMessage) -> MessageKey {
+        let existing = message
+            .message_id
+            .filter(|_| message.kind == MessageKind::Message)
+            .and_then(|id| self.by_id.get(&id))
+            .and_then(|keys| {
+                keys.iter()
+                    .find(|it| self.messages[it].kind == MessageKind::Message)
+                    .copied()
+            });
+        if let Some(key) = existing
+            && let Some(old) = self.messages.get_mut(&key)
+        {
+            *old = MakaiMessage {
+                timestamp: key.timestamp,
+                exchange: message.exchange.or(old.exchange),
+                ..message
+            };
+            return key;
+        }
+
+        let key = MessageKey {
+            timestamp: message.timestamp,
+            seq: self.next_seq,
+        };
+        self.next_seq += 1;
+        self.insert_at(key, message);
+
+        key
+    }
+
+    pub fn remove(&mut self, key: &MessageKey) -> Option<MakaiMessage> {
+        let message = self.messages.remove(key)?;
+        self.unindex(key, &message);
+
+        Some(message)
+    }
+
+    /// Removes every entry referring to the discord message, returning their keys. Context
+    /// inherited from a parent channel stays
+    pub fn remove_id(&mut self, message_id: MessageId) -> Vec<MessageKey> {
+        let keys = self
+            .by_id
+            .get(&message_id)
+            .into_iter()
+            .flatten()
+            .filter(|it| !self.messages[it].inherited)
+            .copied()
+            .collect::<Vec<_>>();
+        for key in &keys {
+            self.unindex_id(key, message_id);
+            self.messages.remove(key);
+        }
+
+        keys
+    }
+
+    /// Removes the matching messages, returning their keys
+    pub fn remove_where(&mut self, matches: impl Fn(&MakaiMessage) -> bool) -> Vec<MessageKey> {
+        let removed = self
+            .messages
+            .extract_if(.., |_, it| matches(it))
+            .collect::<Vec<_>>();
+        for (key, message) in &removed {
+            self.unindex(key, message);
+        }
+
+        removed.into_iter().map(|(key, _)| key).collect()
+    }
+
+    /// Points an entry at a discord message, eg. once the reply it holds was sent
+    pub fn set_message_id(&mut self, key: &MessageKey, message_id: MessageId) {
+        let Some(message) = self.messages.get_mut(key) else {
+            return;
+        };
+        let previous = message.message_id.replace(message_id);
+
+        if let Some(previous) = previous {
+            self.unindex_id(key, previous);
+        }
+        self.by_id.entry(message_id).or_default().insert(*key);
+    }
+
+    pub fn clear(&mut self) {
+        self.messages.clear();
+        self.by_id.clear();
+    }
+
+    fn insert_at(&mut self, key: MessageKey, message: MakaiMessage) {
+        if let Some(id) = message.message_id {
+            self.by_id.entry(id).or_default().insert(key);
+        }
+        self.messages.insert(key, message);
+    }
+
+    fn unindex(&mut self, key: &MessageKey, message: &MakaiMessage) {
+        if let Some(id) = message.message_id {
+            self.unindex_id(key, id);
+        }
+    }
+
+    fn unindex_id(&mut self, key: &MessageKey, message_id: MessageId) {
+        if let Some(keys) = self.by_id.get_mut(&message_id) {
+            keys.remove(key);
+            if keys.is_empty() {
+                self.by_id.remove(&message_id);
+            }
+        }
+    }
+}
+
+impl From<Vec<StoredMessage>> for MessageLog {
+    fn from(value: Vec<StoredMessage>) -> Self {
+        let mut log = MessageLog::default();
+        for StoredMessage { seq, message } in value {
+            let key = MessageKey {
+                timestamp: message.timestamp,
+                seq,
+            };
+            log.next_seq = log.next_seq.max(seq + 1);
+            log.insert_at(key, message);
+        }
+
+        log
+    }
+}
+
+impl From<MessageLog> for Vec<StoredMessage> {
+    fn from(value: MessageLog) -> Self {
+        value
+            .messages
+            .into_iter()
+            .map(|(key, message)| StoredMessage {
+                seq: key.seq,
+                message,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::ai::MessageSender;
+
+    fn message(id: Option<u64>, second: u32, content: &str) -> MakaiMessage {
+        MakaiMessage {
+            message_id: id.map(MessageId::new),
+            timestamp: Utc.with_ymd_and_hms(2026, 1, 1, 12, 0, second).unwrap(),
+            sender: MessageSender::MakaiBot,
+            content: content.to_string(),
+            exchange: None,
+            kind: MessageKind::Message,
+            inherited: false,
+        }
+    }
+
+    fn contents(log: &MessageLog) -> Vec<&str> {
+        log.values().map(|it| it.content.as_str()).collect()
+    }
+
+    #[test]
+    fn same_instant_keeps_both() {
+        let mut log = MessageLog::default();
+        let first = log.insert(message(None, 0, "a"));
+        let second = log.insert(message(None, 0, "b"));
+
+        assert_ne!(first, second);
+        assert_eq!(contents(&log), ["a", "b"]);
+    }
+
+    #[test]
+    fn known_id_updates_in_place() {
+        let mut log = MessageLog::default();
+        let key = log.insert(MakaiMessage {
+            exchange: Some(7),
+            ..message(Some(1), 0, "before")
+        });
+        log.insert(message(Some(2), 1, "other"));
+
+        // Edits arrive with a newer timestamp and without the exchange
+        let updated = log.insert(message(Some(1), 5, "after"));
+
+        assert_eq!(updated, key);
+        assert_eq!(contents(&log), ["after", "other"]);
+        assert_eq!(log.get(&key).unwrap().exchange, Some(7));
+        assert_eq!(log.get(&key).unwrap().timestamp, key.timestamp);
+    }
+
+    #[test]
+    fn remove_id_takes_reactions_along() {
+        let mut log = MessageLog::default();
+        log.insert(message(Some(1), 0, "hi"));
+        log.insert(MakaiMessage::from_reaction(
+            MessageId::new(1),
+            "👋".to_string(),
+        ));
+        log.insert(message(Some(2), 1, "bye"));
+
+        assert_eq!(log.remove_id(MessageId::new(1)).len(), 2);
+        assert_eq!(contents(&log), ["bye"]);
+        assert!(log.remove_id(MessageId::new(1)).is_empty());
+    }
+
+    #[test]
+    fn remove_id_keeps_inherited() {
+        let mut log = MessageLog::default();
+        log.insert(MakaiMessage {
+            inherited: true,
+            ..message(Some(1), 0, "parent")
+        });
+
+        assert!(log.remove_id(MessageId::new(1)).is_empty());
+        assert_eq!(contents(&log), ["parent"]);
+    }
+
+    #[test]
+    fn remove_where_unindexes() {
+        let mut log = MessageLog::default();
+        log.insert(message(Some(1), 0, "a"));
+        log.insert(message(Some(2), 1, "b"));
+        log.insert(message(None, 2, "c"));
+
+        let removed = log.remove_where(|it| it.content != "b");
+
+        assert_eq!(removed.len(), 2);
+        assert_eq!(contents(&log), ["b"]);
+        // The id is free again, so it's added rather than updating what was removed
+        log.insert(message(Some(1), 3, "a again"));
+        assert_eq!(contents(&log), ["b", "a again"]);
+    }
+}
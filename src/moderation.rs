@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+use std::env;
+
+use anyhow::Context as _;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serenity::all::{Channel, ChannelId, Context, CreateAllowedMentions, CreateMessage, GuildId};
+use tracing::{info, warn};
+
+/// Moderation config, read from `LLM_MODERATION_FILE` on every request so edits apply
+/// immediately. If the file doesn't exist moderation is disabled
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModerationConfig {
+    /// An openai compatible moderation endpoint, eg. `https://api.openai.com/v1`
+    pub endpoint: Option<ModerationEndpoint>,
+    /// Rules used for guilds without their own rules, and for DMs
+    pub rules: ModerationRules,
+    /// Rules used instead of `rules` in channels flagged as NSFW
+    pub nsfw_rules: Option<ModerationRules>,
+    pub guilds: HashMap<GuildId, GuildModerationConfig>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuildModerationConfig {
+    pub rules: Option<ModerationRules>,
+    pub nsfw_rules: Option<ModerationRules>,
+    /// Channel that moderation decisions are reported to
+    pub log_channel: Option<ChannelId>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModerationEndpoint {
+    pub url: String,
+    /// Falls back to `LLM_API_KEY`
+    pub api_key: Option<String>,
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModerationRules {
+    /// Case insensitive substrings that are blocked
+    pub keywords: Vec<String>,
+    /// Case insensitive regexes that are blocked
+    pub patterns: Vec<String>,
+    /// Whether to also check against the moderation endpoint
+    pub use_endpoint: bool,
+    /// The in character reply sent instead of running the llm on a blocked input
+    pub refusal: String,
+    /// How many times to regenerate a blocked output before giving up
+    pub regenerate_attempts: usize,
+    /// Sent instead of a blocked output once all regenerations are used up
+    pub replacement: String,
+}
+
+impl Default for ModerationRules {
+    fn default() -> Self {
+        Self {
+            keywords: Vec::new(),
+            patterns: Vec::new(),
+            use_endpoint: true,
+            refusal: "nah i aint touchin dat one".to_string(),
+            regenerate_attempts: 2,
+            replacement: "uhhhh i forgor wat i was gonna say".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModerationStage {
+    Input,
+    Output,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Allowed,
+    Blocked { reason: String },
+}
+
+/// The moderation rules that apply to one channel
+pub struct Moderator {
+    discord_ctx: Context,
+    guild_id: Option<GuildId>,
+    channel_id: ChannelId,
+    nsfw: bool,
+    endpoint: Option<ModerationEndpoint>,
+    rules: Option<ModerationRules>,
+    patterns: Vec<Regex>,
+    log_channel: Option<ChannelId>,
+}
+
+impl Moderator {
+    pub async fn for_channel(
+        discord_ctx: &Context,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+    ) -> anyhow::Result<Self> {
+        let config = load_config().await.context("Load moderation config")?;
+        let nsfw = is_nsfw(discord_ctx, guild_id, channel_id).await;
+
+        let guild = guild_id.and_then(|it| config.guilds.get(&it));
+        let normal_rules = guild
+            .and_then(|it| it.rules.as_ref())
+            .or(config.rules_if_enabled());
+        let rules = if nsfw {
+            guild
+                .and_then(|it| it.nsfw_rules.as_ref())
+                .or(config.nsfw_rules.as_ref())
+                .cloned()
+                // Without explicit NSFW rules, keep the blocklists but skip the endpoint
+                // as it mostly flags things NSFW channels are fine with
+                .or_else(|| {
+                    normal_rules.cloned().map(|it| ModerationRules {
+                        use_endpoint: false,
+                        ..it
+                    })
+                })
+        } else {
+            normal_rules.cloned()
+        };
+
+        let patterns = rules
+            .iter()
+            .flat_map(|it| it.patterns.iter())
+            .filter_map(
+                |it| match RegexBuilder::new(it).case_insensitive(true).build() {
+                    Ok(regex) => Some(regex),
+                    Err(err) => {
+                        warn!("Invalid moderation pattern `{it}`: {err}");
+                        None
+                    }
+                },
+            )
+            .collect();
+
+        Ok(Self {
+            discord_ctx: discord_ctx.clone(),
+            guild_id,
+            channel_id,
+            nsfw,
+            endpoint: config.endpoint,
+            rules,
+            patterns,
+            log_channel: guild.and_then(|it| it.log_channel),
+        })
+    }
+
+    pub fn refusal(&self) -> String {
+        self.rules
+            .as_ref()
+            .map(|it| it.refusal.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn replacement(&self) -> String {
+        self.rules
+            .as_ref()
+            .map(|it| it.replacement.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn regenerate_attempts(&self) -> usize {
+        self.rules
+            .as_ref()
+            .map(|it| it.regenerate_attempts)
+            .unwrap_or_default()
+    }
+
+    pub async fn check(&self, stage: ModerationStage, content: &str) -> Verdict {
+        let verdict = self.verdict(content).await;
+        self.log(stage, content, &verdict).await;
+
+        verdict
+    }
+
+    async fn verdict(&self, content: &str) -> Verdict {
+        let Some(rules) = &self.rules else {
+            return Verdict::Allowed;
+        };
+
+        let lowercase = content.to_lowercase();
+        if let Some(keyword) = rules
+            .keywords
+            .iter()
+            .find(|it| lowercase.contains(&it.to_lowercase()))
+        {
+            return Verdict::Blocked {
+                reason: format!("keyword `{keyword}`"),
+            };
+        }
+
+        if let Some(pattern) = self.patterns.iter().find(|it| it.is_match(content)) {
+            return Verdict::Blocked {
+                reason: format!("pattern `{pattern}`"),
+            };
+        }
+
+        if rules.use_endpoint
+            && let Some(endpoint) = &self.endpoint
+        {
+            match check_endpoint(endpoint, content).await {
+                Ok(categories) if categories.is_empty() => {}
+                Ok(categories) => {
+                    return Verdict::Blocked {
+                        reason: format!("endpoint flagged {}", categories.join(", ")),
+                    };
+                }
+                // Failing open, the blocklists still apply
+                Err(err) => warn!("Moderation endpoint error: {err:?}"),
+            }
+        }
+
+        Verdict::Allowed
+    }
+
+    async fn log(&self, stage: ModerationStage, content: &str, verdict: &Verdict) {
+        info!(
+            target: "makai::moderation",
+            guild = ?self.guild_id,
+            channel = %self.channel_id,
+            nsfw = self.nsfw,
+            ?stage,
+            ?verdict,
+            "Moderation decision"
+        );
+
+        let (Some(log_channel), Verdict::Blocked { reason }) = (self.log_channel, verdict) else {
+            return;
+        };
+
+        let mut excerpt = content.chars().take(300).collect::<String>();
+        if excerpt.len() < content.len() {
+            excerpt.push('…');
+        }
+
+        let message = CreateMessage::new()
+            .content(format!(
+                "Blocked {} in <#{}> ({reason}):\n>>> {excerpt}",
+                match stage {
+                    ModerationStage::Input => "input",
+                    ModerationStage::Output => "output",
+                },
+                self.channel_id,
+            ))
+            .allowed_mentions(CreateAllowedMentions::new());
+        if let Err(err) = log_channel
+            .send_message(&self.discord_ctx.http, message)
+            .await
+        {
+            warn!("Cannot send moderation log: {err:?}");
+        }
+    }
+}
+
+impl ModerationConfig {
+    fn rules_if_enabled(&self) -> Option<&ModerationRules> {
+        let ModerationRules {
+            keywords,
+            patterns,
+            use_endpoint,
+            ..
+        } = &self.rules;
+
+        if keywords.is_empty() && patterns.is_empty() && !(*use_endpoint && self.endpoint.is_some())
+        {
+            None
+        } else {
+            Some(&self.rules)
+        }
+    }
+}
+
+async fn load_config() -> anyhow::Result<ModerationConfig> {
+    let path = env::var("LLM_MODERATION_FILE").unwrap_or_else(|_| "./moderation.json".to_string());
+
+    let exists = tokio::fs::try_exists(&path)
+        .await
+        .context("Check if moderation config exists")?;
+    if !exists {
+        return Ok(ModerationConfig::default());
+    }
+
+    let config = tokio::fs::read_to_string(&path)
+        .await
+        .context("Read moderation config")?;
+    serde_json::from_str(&config).context("Parse moderation config")
+}
+
+async fn is_nsfw(discord_ctx: &Context, guild_id: Option<GuildId>, channel_id: ChannelId) -> bool {
+    let cached = guild_id
+        .and_then(|it| discord_ctx.cache.guild(it))
+        .and_then(|guild| {
+            let channel = guild
+                .channels
+                .get(&channel_id)
+                .or_else(|| guild.threads.iter().find(|it| it.id == channel_id))?;
+
+            // Threads take their NSFW flag from their parent
+            match channel.parent_id.and_then(|it| guild.channels.get(&it)) {
+                Some(parent) if channel.thread_metadata.is_some() => Some(parent.nsfw),
+                _ => Some(channel.nsfw),
+            }
+        });
+    if let Some(nsfw) = cached {
+        return nsfw;
+    }
+
+    match channel_id.to_channel(discord_ctx).await {
+        Ok(Channel::Guild(channel)) => channel.nsfw,
+        Ok(_) => false,
+        Err(err) => {
+            warn!("Cannot fetch channel to check NSFW flag: {err:?}");
+            false
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ModerationResponse {
+    results: Vec<ModerationResult>,
+}
+
+#[derive(Deserialize)]
+struct ModerationResult {
+    flagged: bool,
+    #[serde(default)]
+    categories: HashMap<String, bool>,
+}
+
+/// Returns the categories flagged by the endpoint, empty if the content is fine
+async fn check_endpoint(
+    endpoint: &ModerationEndpoint,
+    content: &str,
+) -> anyhow::Result<Vec<String>> {
+    let api_key = endpoint
+        .api_key
+        .clone()
+        .or_else(|| env::var("LLM_API_KEY").ok())
+        .unwrap_or_else(|| "fake-api-key".to_string());
+
+    let mut body = serde_json::json!({ "input": content });
+    if let Some(model) = &endpoint.model {
+        body["model"] = model.clone().into();
+    }
+
+    let response: ModerationResponse = reqwest::Client::new()
+        .post(format!(
+            "{}/moderations",
+            endpoint.url.trim_end_matches('/')
+        ))
+        .bearer_auth(api_key)
+        .json(&body)
+        .send()
+        .await
+        .context("Send moderation request")?
+        .error_for_status()
+        .context("Moderation request failed")?
+        .json()
+        .await
+        .context("Parse moderation response")?;
+
+    Ok(response
+        .results
+        .into_iter()
+        .filter(|it| it.flagged)
+        .flat_map(|it| {
+            let mut categories = it
+                .categories
+                .into_iter()
+                .filter(|(_, flagged)| *flagged)
+                .map(|(category, _)| category)
+                .collect::<Vec<_>>();
+            if categories.is_empty() {
+                categories.push("unknown".to_string());
+            }
+            categories
+        })
+        .collect())
+}
@@ -0,0 +1,48 @@
+use serenity::all::{Context, Message};
+use tracing::debug;
+
+use crate::ai::MakaiMessage;
+use crate::context::MakaiContext;
+
+/// Whether makai is listening to a message, ie. it's from a human who hasn't opted out and
+/// its channel has passive listening on
+pub async fn is_listening(bot_ctx: &MakaiContext, message: &Message) -> bool {
+    if message.author.bot || (message.content.is_empty() && message.embeds.is_empty()) {
+        return false;
+    }
+
+    let passive = bot_ctx
+        .channel_settings(message.guild_id, &message.channel_id)
+        .await
+        .passive
+        .unwrap_or(false);
+
+    passive && !bot_ctx.is_opted_out(&message.author.id).await
+}
+
+/// Records a message into its channel's memory if makai is listening to it
+pub async fn record_message(bot_ctx: &MakaiContext, discord_ctx: &Context, message: &Message) {
+    if !is_listening(bot_ctx, message).await {
+        return;
+    }
+
+    let Some(bot_user) = bot_ctx.user().await else {
+        return;
+    };
+
+    debug!(
+        "Passively recording message {} in {}",
+        message.id, message.channel_id
+    );
+
+    let retention = bot_ctx
+        .retention(message.guild_id, &message.channel_id)
+        .await;
+    let channel = bot_ctx.memory(message.guild_id, &message.channel_id).await;
+    let message =
+        MakaiMessage::from_message(&discord_ctx.cache, bot_user.id, message.guild_id, message);
+
+    channel
+        .add_message_with_retention(message, &retention)
+        .await;
+}
@@ -0,0 +1,285 @@
+use std::env;
+
+use anyhow::{Context as _, bail};
+use chrono::{DateTime, Utc};
+use llm::chat::{ChatMessage, StructuredOutputFormat};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use serenity::all::{ChannelId, Context, GetMessages, GuildId, MessageId, UserId};
+use tracing::{debug, info};
+
+use crate::ai::{self, MakaiMessage};
+use crate::context::MakaiContext;
+use crate::settings::{GenerationSettings, persona_path};
+
+/// At most this many of the user's messages are shown to the llm
+const MAX_SAMPLE_MESSAGES: usize = 300;
+/// Keeps the sample within a reasonable prompt size
+const MAX_SAMPLE_CHARS: usize = 20_000;
+const MAX_PHRASES: usize = 60;
+
+/// A user's agreement to have personas built from their messages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PersonaConsent {
+    pub granted_at: DateTime<Utc>,
+    /// Personas built from the user's messages, deleted if they revoke consent
+    #[serde(default)]
+    pub personas: Vec<String>,
+}
+
+impl PersonaConsent {
+    pub fn new() -> Self {
+        Self {
+            granted_at: Utc::now(),
+            personas: Vec::new(),
+        }
+    }
+}
+
+impl Default for PersonaConsent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Where to collect a user's messages from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PersonaSource {
+    /// Messages already in memory for the guild's channels
+    Memory,
+    /// Messages read back from a channel's history through the discord api
+    History(ChannelId),
+}
+
+#[derive(Debug, Deserialize)]
+struct PersonaTraits {
+    phrases: Vec<String>,
+    style: String,
+}
+
+/// What a build produced
+pub struct BuiltPersona {
+    pub sample_size: usize,
+    pub phrases: usize,
+}
+
+/// Collects the user's messages from `source` and writes a persona named `name` from them
+pub async fn build_persona(
+    bot_ctx: &MakaiContext,
+    discord_ctx: &Context,
+    settings: &GenerationSettings,
+    guild_id: GuildId,
+    user: UserId,
+    name: &str,
+    source: PersonaSource,
+) -> anyhow::Result<BuiltPersona> {
+    if bot_ctx.persona_consent(&user).await.is_none() {
+        bail!("That user hasn't agreed to have a persona built, they can with `/persona consent`");
+    }
+
+    let path = persona_path(name);
+    let owner = bot_ctx.persona_owner(name).await;
+    if owner.is_some_and(|it| it != user)
+        || (owner.is_none() && tokio::fs::try_exists(&path).await.unwrap_or(false))
+    {
+        bail!("A persona named `{name}` already exists");
+    }
+
+    let messages = match source {
+        PersonaSource::Memory => {
+            let channels = discord_ctx
+                .cache
+                .guild(guild_id)
+                .map(|it| it.channels.keys().copied().collect::<Vec<_>>())
+                .unwrap_or_default();
+
+            bot_ctx
+                .user_messages(user, guild_id, |it| channels.contains(it))
+                .await
+        }
+        PersonaSource::History(channel) => {
+            collect_history(bot_ctx, discord_ctx, guild_id, channel, user).await?
+        }
+    };
+    if messages.is_empty() {
+        bail!("Found no messages from that user");
+    }
+
+    let sample = sample(messages);
+    let traits = extract_traits(settings, &sample).await?;
+
+    let base_prompt_file =
+        env::var("LLM_PROMPT_FILE").unwrap_or_else(|_| "./prompt.txt".to_string());
+    let base_prompt = tokio::fs::read_to_string(base_prompt_file)
+        .await
+        .context("Read prompt file")?;
+    let phrases = traits
+        .phrases
+        .iter()
+        .take(MAX_PHRASES)
+        .fold(String::new(), |mut acc, it| {
+            acc.push_str(&format!("- {}\n", it.trim()));
+            acc
+        });
+    let prompt = format!(
+        "{}\n\nYou are imitating one specific member of the group. This is how they write:\n{}\n",
+        base_prompt.replace("{WORDS}", &phrases),
+        traits.style.trim()
+    );
+
+    if let Some(dir) = std::path::Path::new(&path).parent() {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .context("Create persona dir")?;
+    }
+    tokio::fs::write(&path, prompt)
+        .await
+        .context("Write persona file")?;
+    if !bot_ctx.add_derived_persona(&user, name.to_string()).await {
+        // Consent was revoked while the persona was being built
+        delete_persona(bot_ctx, name).await?;
+        bail!("That user revoked their consent");
+    }
+
+    info!("Built persona `{name}` from {} messages", sample.len());
+
+    Ok(BuiltPersona {
+        sample_size: sample.len(),
+        phrases: traits.phrases.len().min(MAX_PHRASES),
+    })
+}
+
+/// Deletes a persona's prompt file and stops every channel from using it
+pub async fn delete_persona(bot_ctx: &MakaiContext, name: &str) -> anyhow::Result<()> {
+    bot_ctx.clear_persona(name).await;
+
+    match tokio::fs::remove_file(persona_path(name)).await {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err).context("Delete persona file"),
+    }
+}
+
+/// Pages back through a channel's history for the user's messages
+async fn collect_history(
+    bot_ctx: &MakaiContext,
+    discord_ctx: &Context,
+    guild_id: GuildId,
+    channel: ChannelId,
+    user: UserId,
+) -> anyhow::Result<Vec<MakaiMessage>> {
+    let limit = env::var("LLM_PERSONA_HISTORY_LIMIT")
+        .ok()
+        .and_then(|it| it.parse::<usize>().ok())
+        .unwrap_or(2000);
+    let bot_user = bot_ctx.user().await.context("Bot user is not known yet")?;
+
+    let mut messages = Vec::new();
+    let mut before: Option<MessageId> = None;
+    let mut scanned = 0;
+    while scanned < limit && messages.len() < MAX_SAMPLE_MESSAGES {
+        let mut request = GetMessages::new().limit(100);
+        if let Some(before) = before {
+            request = request.before(before);
+        }
+
+        let page = channel
+            .messages(&discord_ctx.http, request)
+            .await
+            .context("Read channel history")?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        before = Some(last.id);
+        scanned += page.len();
+
+        messages.extend(
+            page.iter()
+                .filter(|it| it.author.id == user && !it.content.is_empty())
+                .map(|it| {
+                    MakaiMessage::from_message(&discord_ctx.cache, bot_user.id, Some(guild_id), it)
+                }),
+        );
+    }
+
+    debug!(
+        "Scanned {scanned} messages in {channel}, found {} from {user}",
+        messages.len()
+    );
+
+    Ok(messages)
+}
+
+/// The most recent messages that fit in the sample limits
+fn sample(mut messages: Vec<MakaiMessage>) -> Vec<String> {
+    // Neither source is in order, history is paged backwards and memory spans many channels
+    messages.sort_by_key(|it| std::cmp::Reverse(it.timestamp));
+    let mut chars = 0;
+
+    messages
+        .into_iter()
+        .map(|it| it.content)
+        .take(MAX_SAMPLE_MESSAGES)
+        .take_while(|it| {
+            chars += it.chars().count();
+            chars <= MAX_SAMPLE_CHARS
+        })
+        .collect()
+}
+
+async fn extract_traits(
+    settings: &GenerationSettings,
+    sample: &[String],
+) -> anyhow::Result<PersonaTraits> {
+    let prompt_file = env::var("LLM_PERSONA_BUILDER_PROMPT_FILE")
+        .unwrap_or_else(|_| "./persona_builder_prompt.txt".to_string());
+    let system = tokio::fs::read_to_string(prompt_file)
+        .await
+        .context("Read persona builder prompt file")?;
+
+    let schema = StructuredOutputFormat {
+        name: "persona".to_string(),
+        description: Some("Characteristic phrases and a style summary".to_string()),
+        schema: Some(json!({
+            "type": "object",
+            "properties": {
+                "phrases": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "maxItems": MAX_PHRASES,
+                },
+                "style": { "type": "string" },
+            },
+            "required": ["phrases", "style"],
+            "additionalProperties": false,
+        })),
+        strict: Some(true),
+    };
+    // The summary needs more room than a chat reply
+    let settings = GenerationSettings {
+        max_tokens: None,
+        ..settings.clone()
+    };
+    let llm = ai::llm_builder(&settings, system)?
+        .schema(schema)
+        .build()
+        .context("Failed to build LLM")?;
+
+    let content = sample.iter().fold(String::new(), |mut acc, it| {
+        acc.push_str(&format!("- {}\n", it.replace('\n', " ")));
+        acc
+    });
+    let response = llm
+        .chat(&[ChatMessage::user().content(content).build()])
+        .await
+        .context("LLM Error")?;
+
+    let text = response.text().unwrap_or_default();
+    let text = text
+        .trim()
+        .trim_start_matches("```json")
+        .trim_matches('`')
+        .trim();
+
+    serde_json::from_str(text).context("Parse persona traits")
+}
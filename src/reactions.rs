@@ -0,0 +1,241 @@
+use std::env;
+
+use anyhow::Context as _;
+use llm::chat::{ChatMessage, StructuredOutputFormat};
+use serde::Deserialize;
+use serde_json::json;
+use serenity::all::{Context, GuildId, Message, ReactionType};
+use tracing::{debug, error};
+
+use crate::ai::{self, MakaiMessage, NameResolver};
+use crate::context::{MakaiContext, MakaiContextChannel};
+use crate::moderation::{ModerationStage, Moderator, Verdict};
+use crate::passive;
+use crate::settings::{ChannelSettings, GenerationSettings};
+
+/// Discord allows more, but a wall of reactions stops being funny
+const MAX_REACTIONS: usize = 3;
+/// Reactions only need a little context to land
+const REACT_HISTORY_LENGTH: usize = 10;
+
+#[derive(Debug, Deserialize)]
+struct ReactionChoice {
+    emoji: Vec<String>,
+}
+
+/// An emoji makai picked, with how it's shown in memory
+pub struct Reaction {
+    pub reaction: ReactionType,
+    pub name: String,
+}
+
+/// Asks the llm which emoji, if any, makai would react to `target` with. Messages
+/// moderation blocks get no reaction at all
+pub async fn pick_reactions(
+    discord_ctx: &Context,
+    settings: &GenerationSettings,
+    moderator: &Moderator,
+    channel: &MakaiContextChannel,
+    guild_id: Option<GuildId>,
+    target: &MakaiMessage,
+) -> anyhow::Result<Vec<Reaction>> {
+    if let Verdict::Blocked { .. } = moderator
+        .check(ModerationStage::Input, &target.content)
+        .await
+    {
+        return Ok(Vec::new());
+    }
+
+    let custom_emoji = guild_id
+        .and_then(|it| discord_ctx.cache.guild(it))
+        .map(|guild| {
+            guild
+                .emojis
+                .values()
+                .filter(|it| it.available)
+                .map(|it| (it.name.clone(), it.id, it.animated))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let prompt_file =
+        env::var("LLM_REACT_PROMPT_FILE").unwrap_or_else(|_| "./react_prompt.txt".to_string());
+    let emoji_list = if custom_emoji.is_empty() {
+        "None".to_string()
+    } else {
+        custom_emoji
+            .iter()
+            .map(|(name, _, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let system = ai::load_prompt(&prompt_file, &settings.words)
+        .await?
+        .replace("{EMOJI}", &emoji_list);
+
+    let schema = StructuredOutputFormat {
+        name: "reactions".to_string(),
+        description: Some("The emoji to react with".to_string()),
+        schema: Some(json!({
+            "type": "object",
+            "properties": {
+                "emoji": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "maxItems": MAX_REACTIONS,
+                }
+            },
+            "required": ["emoji"],
+            "additionalProperties": false,
+        })),
+        strict: Some(true),
+    };
+    let llm = ai::llm_builder(settings, system)?
+        .schema(schema)
+        .build()
+        .context("Failed to build LLM")?;
+
+    let names = NameResolver {
+        cache: &discord_ctx.cache,
+        guild_id,
+    };
+    let mut messages = channel.chat_messages(REACT_HISTORY_LENGTH, names).await;
+    messages.push(
+        ChatMessage::user()
+            .content(format!(
+                "React to this message: {}",
+                target.to_chat_message(names).content
+            ))
+            .build(),
+    );
+
+    let response = llm.chat(&messages).await.context("LLM Error")?;
+    let text = response.text().unwrap_or_default();
+    let text = text
+        .trim()
+        .trim_start_matches("```json")
+        .trim_matches('`')
+        .trim();
+    let choice: ReactionChoice =
+        serde_json::from_str(text).with_context(|| format!("Parse reactions `{text}`"))?;
+
+    let mut reactions: Vec<Reaction> = Vec::new();
+    for emoji in choice.emoji {
+        let emoji = emoji.trim();
+        let name = emoji.trim_matches(':');
+
+        let reaction =
+            if let Some((name, id, animated)) = custom_emoji.iter().find(|(it, _, _)| it == name) {
+                Reaction {
+                    reaction: ReactionType::Custom {
+                        animated: *animated,
+                        id: *id,
+                        name: Some(name.clone()),
+                    },
+                    name: format!(":{name}:"),
+                }
+            } else if !emoji.is_empty() && !emoji.chars().any(|it| it.is_ascii_alphanumeric()) {
+                Reaction {
+                    reaction: ReactionType::Unicode(emoji.to_string()),
+                    name: emoji.to_string(),
+                }
+            } else {
+                debug!("Ignoring unknown emoji `{emoji}`");
+                continue;
+            };
+
+        if !reactions.iter().any(|it| it.name == reaction.name) {
+            reactions.push(reaction);
+        }
+    }
+    reactions.truncate(MAX_REACTIONS);
+
+    Ok(reactions)
+}
+
+/// Adds the reactions to `message` and records them in memory, returns the ones that stuck
+pub async fn react(
+    discord_ctx: &Context,
+    channel: &MakaiContextChannel,
+    message: &Message,
+    reactions: Vec<Reaction>,
+) -> Vec<Reaction> {
+    let mut added = Vec::new();
+    for reaction in reactions {
+        match message
+            .react(&discord_ctx.http, reaction.reaction.clone())
+            .await
+        {
+            Ok(_) => added.push(reaction),
+            Err(err) => error!("Cannot react with `{}`: {err:?}", reaction.name),
+        }
+    }
+
+    if !added.is_empty() {
+        let emoji = added
+            .iter()
+            .map(|it| it.name.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        channel
+            .add_message(MakaiMessage::from_reaction(message.id, emoji))
+            .await;
+    }
+
+    added
+}
+
+/// Rolls whether to react to a message in a passive channel, and reacts if so
+pub async fn maybe_react(
+    bot_ctx: &MakaiContext,
+    discord_ctx: &Context,
+    message: &Message,
+) -> anyhow::Result<()> {
+    if !passive::is_listening(bot_ctx, message).await {
+        return Ok(());
+    }
+
+    let Some(bot_user) = bot_ctx.user().await else {
+        return Ok(());
+    };
+
+    let chance = bot_ctx
+        .channel_settings(message.guild_id, &message.channel_id)
+        .await
+        .or(&ChannelSettings::from_env()?)
+        .react_chance
+        .unwrap_or(0.0);
+    if chance <= 0.0 || rand::random::<f32>() >= chance {
+        return Ok(());
+    }
+
+    debug!(
+        "Reacting to message {} in {}",
+        message.id, message.channel_id
+    );
+
+    let settings = bot_ctx
+        .generation_settings(message.guild_id, &message.channel_id)
+        .await
+        .context("Resolve settings")?;
+    let channel = bot_ctx.memory(message.guild_id, &message.channel_id).await;
+    let target =
+        MakaiMessage::from_message(&discord_ctx.cache, bot_user.id, message.guild_id, message);
+
+    let moderator = Moderator::for_channel(discord_ctx, message.guild_id, message.channel_id)
+        .await
+        .context("Load moderator")?;
+    let reactions = pick_reactions(
+        discord_ctx,
+        &settings,
+        &moderator,
+        &channel,
+        message.guild_id,
+        &target,
+    )
+    .await
+    .context("Pick reactions")?;
+    react(discord_ctx, &channel, message, reactions).await;
+
+    Ok(())
+}
@@ -0,0 +1,180 @@
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::message_log::{MessageKey, MessageLog};
+use crate::settings::ChannelSettings;
+
+/// How much of a channel's memory is kept, applied whenever a message is added and by the
+/// periodic sweeper
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    pub max_messages: Option<usize>,
+    pub max_age: Option<TimeDelta>,
+    /// Memory is cleared once the newest message is older than this
+    pub inactive_after: Option<TimeDelta>,
+}
+
+impl RetentionPolicy {
+    /// Reads the policy from resolved settings, where 0 means no limit
+    pub fn from_settings(settings: &ChannelSettings) -> Self {
+        let days = |days: Option<u64>| {
+            days.filter(|it| *it > 0)
+                .and_then(|it| TimeDelta::try_days(it.try_into().ok()?))
+        };
+
+        RetentionPolicy {
+            max_messages: settings.retention_count.filter(|it| *it > 0),
+            max_age: days(settings.retention_days),
+            inactive_after: days(settings.inactive_days),
+        }
+    }
+
+    /// The keys of the messages this policy drops at `now`. Context a thread inherited from
+    /// its parent only goes once the whole memory is inactive
+    pub fn expired(&self, messages: &MessageLog, now: DateTime<Utc>) -> Vec<MessageKey> {
+        let Some(newest) = messages.keys().next_back() else {
+            return Vec::new();
+        };
+
+        if self
+            .inactive_after
+            .is_some_and(|inactive_after| newest.timestamp < now - inactive_after)
+        {
+            return messages.keys().copied().collect();
+        }
+
+        let own = messages
+            .iter()
+            .filter(|(_, it)| !it.inherited)
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        let too_old = self
+            .max_age
+            .map(|max_age| {
+                let cutoff = now - max_age;
+                own.partition_point(|it| it.timestamp < cutoff)
+            })
+            .unwrap_or_default();
+        let too_many = self
+            .max_messages
+            .map(|max_messages| own.len().saturating_sub(max_messages))
+            .unwrap_or_default();
+
+        // Messages are ordered by time, so both limits drop from the front
+        own.into_iter().take(too_old.max(too_many)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::ai::{MakaiMessage, MessageKind, MessageSender};
+
+    fn day(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 1, day, 12, 0, 0).unwrap()
+    }
+
+    /// One message per given day
+    fn log(days: &[u32]) -> MessageLog {
+        let mut log = MessageLog::default();
+        for it in days {
+            log.insert(MakaiMessage {
+                message_id: None,
+                timestamp: day(*it),
+                sender: MessageSender::MakaiBot,
+                content: it.to_string(),
+                exchange: None,
+                kind: MessageKind::Message,
+                inherited: false,
+            });
+        }
+        log
+    }
+
+    fn expired_days(policy: &RetentionPolicy, log: &MessageLog, now: DateTime<Utc>) -> Vec<u32> {
+        policy
+            .expired(log, now)
+            .iter()
+            .map(|it| log.get(it).unwrap().content.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn zero_means_no_limit() {
+        let policy = RetentionPolicy::from_settings(&ChannelSettings {
+            retention_count: Some(0),
+            retention_days: Some(0),
+            inactive_days: Some(0),
+            ..Default::default()
+        });
+
+        assert_eq!(policy, RetentionPolicy::default());
+        assert!(expired_days(&policy, &log(&[1, 2, 3]), day(30)).is_empty());
+    }
+
+    #[test]
+    fn max_messages_drops_oldest() {
+        let policy = RetentionPolicy::from_settings(&ChannelSettings {
+            retention_count: Some(2),
+            ..Default::default()
+        });
+
+        assert_eq!(expired_days(&policy, &log(&[1, 2, 3, 4]), day(5)), [1, 2]);
+        assert!(expired_days(&policy, &log(&[1, 2]), day(5)).is_empty());
+    }
+
+    #[test]
+    fn max_age_drops_older_messages() {
+        let policy = RetentionPolicy::from_settings(&ChannelSettings {
+            retention_days: Some(3),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            expired_days(&policy, &log(&[1, 2, 3, 8, 9]), day(10)),
+            [1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn stricter_limit_wins() {
+        let policy = RetentionPolicy::from_settings(&ChannelSettings {
+            retention_count: Some(1),
+            retention_days: Some(5),
+            ..Default::default()
+        });
+
+        assert_eq!(expired_days(&policy, &log(&[1, 8, 9]), day(10)), [1, 8]);
+    }
+
+    #[test]
+    fn inactive_clears_everything() {
+        let policy = RetentionPolicy::from_settings(&ChannelSettings {
+            inactive_days: Some(7),
+            ..Default::default()
+        });
+        let log = log(&[1, 2, 3]);
+
+        assert!(expired_days(&policy, &log, day(9)).is_empty());
+        assert_eq!(expired_days(&policy, &log, day(11)), [1, 2, 3]);
+    }
+
+    #[test]
+    fn inherited_context_is_kept() {
+        let policy = RetentionPolicy::from_settings(&ChannelSettings {
+            retention_count: Some(1),
+            retention_days: Some(3),
+            ..Default::default()
+        });
+        let mut log = log(&[8, 9]);
+        log.insert(MakaiMessage {
+            timestamp: day(1),
+            content: "1".to_string(),
+            inherited: true,
+            ..log.values().next().unwrap().clone()
+        });
+
+        assert_eq!(expired_days(&policy, &log, day(10)), [8]);
+    }
+}
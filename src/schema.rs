@@ -0,0 +1,280 @@
+use anyhow::{Context as _, bail};
+use chrono::DateTime;
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use crate::context::serde::{MakaiContextChannelSerde, MakaiContextSerde};
+
+/// The version of the state layout written by this build. Bump it and add a step to
+/// `MIGRATIONS` whenever a change can't be covered by `#[serde(default)]`
+pub const SCHEMA_VERSION: u32 = 3;
+
+type Migration = fn(&mut Value) -> anyhow::Result<()>;
+
+/// `MIGRATIONS[n]` upgrades a version `n` state to version `n + 1`
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [v0_to_v1, v1_to_v2, v2_to_v3];
+
+/// Every versioned state and memory export is wrapped in this
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    version: u32,
+    state: &'a T,
+}
+
+pub fn encode_state(state: &MakaiContextSerde) -> anyhow::Result<Vec<u8>> {
+    serde_json::to_vec(&Envelope {
+        version: SCHEMA_VERSION,
+        state,
+    })
+    .context("Encode state")
+}
+
+/// Parses a state of any version, upgrading it to the current layout
+pub fn decode_state(data: &[u8]) -> anyhow::Result<MakaiContextSerde> {
+    let value: Value = serde_json::from_slice(data).context("Parse state")?;
+    let (version, state) = open_envelope(value);
+
+    migrate_state(state, version)
+}
+
+/// Upgrades a raw state from `version` to the current layout
+pub fn migrate_state(mut state: Value, version: u32) -> anyhow::Result<MakaiContextSerde> {
+    if version > SCHEMA_VERSION {
+        bail!(
+            "State is from a newer version ({version}) than this build supports ({SCHEMA_VERSION})"
+        );
+    }
+
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(&mut state).with_context(|| format!("Migrate state from version {from}"))?;
+    }
+
+    serde_json::from_value(state).context("Decode state")
+}
+
+/// Encodes one channel's memory, as used by `/memory export`
+pub fn encode_channel(channel: &MakaiContextChannelSerde) -> anyhow::Result<Vec<u8>> {
+    serde_json::to_vec_pretty(&Envelope {
+        version: SCHEMA_VERSION,
+        state: channel,
+    })
+    .context("Encode memory")
+}
+
+/// Parses one channel's memory of any version, upgrading it to the current layout
+pub fn decode_channel(data: &[u8]) -> anyhow::Result<MakaiContextChannelSerde> {
+    let value: Value = serde_json::from_slice(data).context("Parse memory")?;
+    let (version, channel) = open_envelope(value);
+
+    // Migrations work on whole states, so run them on a state holding just this channel
+    let state = json!({ "channels": { "1": channel }, "bot_user": null });
+    let mut state = migrate_state(state, version)?;
+
+    state
+        .channels
+        .drain()
+        .map(|(_, it)| it)
+        .next()
+        .context("Find migrated channel")
+}
+
+/// Splits a document into its version and payload, files from before versioning are version 0
+fn open_envelope(value: Value) -> (u32, Value) {
+    match value {
+        Value::Object(mut object)
+            if object.len() == 2
+                && object.contains_key("state")
+                && object.contains_key("version") =>
+        {
+            let version = object
+                .get("version")
+                .and_then(Value::as_u64)
+                .unwrap_or_default() as u32;
+            let state = object.remove("state").unwrap_or_default();
+
+            (version, state)
+        }
+        value => (0, value),
+    }
+}
+
+/// Version 1 only adds the envelope. Everything added to unversioned state files (settings,
+/// guilds, opt outs, exchanges, reactions, ...) was optional, so serde defaults cover it
+fn v0_to_v1(state: &mut Value) -> anyhow::Result<()> {
+    if !state.is_object() {
+        bail!("Expected state to be an object");
+    }
+
+    Ok(())
+}
+
+/// Version 2 stores each channel's messages as a list with a sequence number, instead of a map
+/// keyed by timestamp where messages from the same instant overwrote each other
+fn v1_to_v2(state: &mut Value) -> anyhow::Result<()> {
+    let Some(channels) = state.get_mut("channels").and_then(Value::as_object_mut) else {
+        return Ok(());
+    };
+
+    for channel in channels.values_mut() {
+        let Some(messages) = channel.get_mut("messages") else {
+            continue;
+        };
+        let Value::Object(by_timestamp) = messages.take() else {
+            bail!("Expected messages to be keyed by timestamp");
+        };
+
+        let mut list = by_timestamp
+            .into_iter()
+            .map(|(timestamp, message)| {
+                let timestamp = DateTime::parse_from_rfc3339(&timestamp)
+                    .with_context(|| format!("Parse message timestamp `{timestamp}`"))?;
+                Ok((timestamp, message))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        list.sort_by_key(|(timestamp, _)| *timestamp);
+
+        *messages = list
+            .into_iter()
+            .enumerate()
+            .map(|(seq, (_, mut message))| {
+                message["seq"] = seq.into();
+                message
+            })
+            .collect();
+    }
+
+    Ok(())
+}
+
+/// Version 3 moves the author's id into the sender, next to the name it had when remembered,
+/// so the names users go by now can be looked up when rendering prompts
+fn v2_to_v3(state: &mut Value) -> anyhow::Result<()> {
+    if let Some(channels) = state.get_mut("channels").and_then(Value::as_object_mut) {
+        for channel in channels.values_mut() {
+            move_author_ids(channel)?;
+        }
+    }
+    if let Some(shared) = state.get_mut("shared").and_then(Value::as_array_mut) {
+        for memory in shared {
+            move_author_ids(memory)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn move_author_ids(memory: &mut Value) -> anyhow::Result<()> {
+    let Some(messages) = memory.get_mut("messages").and_then(Value::as_array_mut) else {
+        return Ok(());
+    };
+
+    for message in messages {
+        let message = message
+            .as_object_mut()
+            .context("Expected message to be an object")?;
+        // Older messages didn't record their author at all, they keep just the name
+        let author_id = message.remove("author_id").unwrap_or_default();
+
+        if let Some(sender) = message.get_mut("sender")
+            && let Some(name) = sender.get("User").and_then(Value::as_str)
+        {
+            *sender = json!({
+                "User": { "id": author_id, "name": name, "nickname": null },
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// Decodes a fixture and compares the re-encoded state with its golden file. Run with
+    /// `UPDATE_GOLDEN=1` to rewrite the golden files after an intended layout change
+    fn check_golden(fixture: &str, decode: impl Fn(&[u8]) -> anyhow::Result<Value>) {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let data = std::fs::read(dir.join(format!("{fixture}.json"))).unwrap();
+        let actual = decode(&data).unwrap();
+
+        let golden_path = dir.join(format!("{fixture}.golden.json"));
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&golden_path, serde_json::to_vec_pretty(&actual).unwrap()).unwrap();
+        }
+
+        let golden: Value = serde_json::from_slice(&std::fs::read(golden_path).unwrap()).unwrap();
+        assert_eq!(
+            actual, golden,
+            "{fixture} no longer matches its golden file"
+        );
+    }
+
+    fn state(data: &[u8]) -> anyhow::Result<Value> {
+        let state = decode_state(data)?;
+
+        // Round trip through the current format too
+        let state = decode_state(&encode_state(&state)?)?;
+        Ok(serde_json::to_value(state)?)
+    }
+
+    fn channel(data: &[u8]) -> anyhow::Result<Value> {
+        let channel = decode_channel(data)?;
+
+        let channel = decode_channel(&encode_channel(&channel)?)?;
+        Ok(serde_json::to_value(channel)?)
+    }
+
+    #[test]
+    fn state_v0_baseline() {
+        check_golden("state_v0_baseline", state);
+    }
+
+    #[test]
+    fn state_v0_settings() {
+        check_golden("state_v0_settings", state);
+    }
+
+    #[test]
+    fn state_v0_personas() {
+        check_golden("state_v0_personas", state);
+    }
+
+    #[test]
+    fn state_v1() {
+        check_golden("state_v1", state);
+    }
+
+    #[test]
+    fn state_v2() {
+        check_golden("state_v2", state);
+    }
+
+    #[test]
+    fn state_v2_shared() {
+        check_golden("state_v2_shared", state);
+    }
+
+    #[test]
+    fn state_v2_facts() {
+        check_golden("state_v2_facts", state);
+    }
+
+    #[test]
+    fn memory_v0() {
+        check_golden("memory_v0", channel);
+    }
+
+    #[test]
+    fn memory_v1() {
+        check_golden("memory_v1", channel);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let data = json!({ "version": SCHEMA_VERSION + 1, "state": {} });
+        assert!(decode_state(data.to_string().as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashSet;
+
+/// How well a candidate reply fits, higher is better
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CandidateScore {
+    pub style: f32,
+    pub novelty: f32,
+    pub length: f32,
+    pub total: f32,
+}
+
+/// Scores candidate replies locally, without another llm call
+pub struct CandidateScorer<'a> {
+    /// The makain word list, using its phrases counts towards style
+    pub words: &'a [String],
+    /// The bot's most recent replies in the channel, repeating them costs novelty
+    pub recent_replies: &'a [String],
+}
+
+impl CandidateScorer<'_> {
+    pub fn score(&self, candidate: &str) -> CandidateScore {
+        let style = self.style(candidate);
+        let novelty = self.novelty(candidate);
+        let length = length_fit(candidate);
+
+        CandidateScore {
+            style,
+            novelty,
+            length,
+            total: 0.45 * style + 0.35 * novelty + 0.2 * length,
+        }
+    }
+
+    /// Makain is all caps or no caps, has no punctuation and uses the word list
+    fn style(&self, candidate: &str) -> f32 {
+        let letters = candidate.chars().filter(|it| it.is_alphabetic());
+        let (upper, lower) = letters.fold((0, 0), |(upper, lower), it| {
+            if it.is_uppercase() {
+                (upper + 1, lower)
+            } else {
+                (upper, lower + 1)
+            }
+        });
+        let caps = if upper == 0 || lower == 0 {
+            1.0
+        } else {
+            upper.max(lower) as f32 / (upper + lower) as f32
+        };
+
+        let punctuation = candidate
+            .chars()
+            .filter(|it| matches!(it, '.' | ',' | ';' | '?' | '!' | '\''))
+            .count();
+        let punctuation = 1.0 / (1.0 + punctuation as f32 * 0.5);
+
+        let lowercase = candidate.to_lowercase();
+        let uses_words = self
+            .words
+            .iter()
+            .map(|it| it.trim().to_lowercase())
+            .filter(|it| it.len() > 2)
+            .any(|it| lowercase.contains(&it));
+        let words = if uses_words { 1.0 } else { 0.5 };
+
+        (caps + punctuation + words) / 3.0
+    }
+
+    /// One minus the highest word overlap with any recent reply
+    fn novelty(&self, candidate: &str) -> f32 {
+        let candidate = word_set(candidate);
+
+        let max_overlap = self
+            .recent_replies
+            .iter()
+            .map(|it| jaccard(&candidate, &word_set(it)))
+            .fold(0.0, f32::max);
+
+        1.0 - max_overlap
+    }
+}
+
+/// Banter should be short, but not empty
+fn length_fit(candidate: &str) -> f32 {
+    let len = candidate.trim().chars().count();
+
+    match len {
+        0 => 0.0,
+        1..=4 => 0.6,
+        5..=120 => 1.0,
+        _ => 120.0 / len as f32,
+    }
+}
+
+fn word_set(text: &str) -> HashSet<String> {
+    text.split_whitespace()
+        .map(|it| {
+            it.trim_matches(|it: char| !it.is_alphanumeric())
+                .to_lowercase()
+        })
+        .filter(|it| !it.is_empty())
+        .collect()
+}
+
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f32 {
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+
+    a.intersection(b).count() as f32 / a.union(b).count() as f32
+}
@@ -0,0 +1,528 @@
+use std::env;
+
+use anyhow::{Context as _, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::facts::UserFacts;
+use crate::mentions::MentionPolicy;
+use crate::retention::RetentionPolicy;
+
+pub const MAX_CANDIDATES: usize = 8;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuildSettings {
+    pub mention_policy: MentionPolicy,
+    /// Defaults for every channel in the guild, overridden by the channel's own settings
+    pub channel_defaults: ChannelSettings,
+    /// Whether words suggested by members need approval before makai uses them
+    pub word_approval: bool,
+}
+
+/// Generation settings that can be overridden per channel. Unset values fall through to the
+/// guild defaults, then to the global env vars
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChannelSettings {
+    pub temperature: Option<f32>,
+    pub history_length: Option<usize>,
+    pub max_tokens: Option<u32>,
+    pub model: Option<String>,
+    pub persona: Option<String>,
+    /// How many candidates to generate for short replies, the best one gets sent
+    pub candidates: Option<usize>,
+    /// Remember every message sent in the channel, not just the ones that get `Remember`ed
+    pub passive: Option<bool>,
+    /// Reply when makai is mentioned or replied to
+    pub auto_reply: Option<bool>,
+    /// Chance of chiming in after a message in a passive channel, from 0 to 1
+    pub chime_in_chance: Option<f32>,
+    /// Minimum seconds between chime ins in a channel
+    pub chime_in_cooldown: Option<u64>,
+    /// Comma separated words that make chiming in more likely
+    pub chime_in_keywords: Option<String>,
+    /// UTC hours during which makai never chimes in, eg. `23-7`
+    pub quiet_hours: Option<QuietHours>,
+    /// Chance of reacting with emoji to a message in a passive channel, from 0 to 1
+    pub react_chance: Option<f32>,
+    /// Most messages kept in memory, 0 keeps every message
+    pub retention_count: Option<usize>,
+    /// Days messages are kept in memory for, 0 keeps them forever
+    pub retention_days: Option<u64>,
+    /// Days without new messages before memory is cleared, 0 never clears it
+    pub inactive_days: Option<u64>,
+    /// How a thread's memory relates to its parent channel's, a thread without its own value
+    /// uses its parent's
+    pub thread_memory: Option<ThreadMemory>,
+    /// Which memory the channel reads and writes, its own, the guild's or a group's
+    pub memory_scope: Option<MemoryScope>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl QuietHours {
+    /// Whether `hour` falls in the quiet hours, which may wrap around midnight
+    pub fn contains(&self, hour: u32) -> bool {
+        if self.start <= self.end {
+            (self.start..self.end).contains(&hour)
+        } else {
+            hour >= self.start || hour < self.end
+        }
+    }
+}
+
+impl std::fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+impl std::str::FromStr for QuietHours {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('-')
+            .context("Expected quiet hours like `23-7`")?;
+        let start = start.trim().parse::<u32>().context("Parse start hour")?;
+        let end = end.trim().parse::<u32>().context("Parse end hour")?;
+        if start > 23 || end > 23 {
+            bail!("Hours must be between 0 and 23");
+        }
+
+        Ok(QuietHours { start, end })
+    }
+}
+
+/// How a thread's memory relates to its parent channel's
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ThreadMemory {
+    /// Starts with a copy of the parent's recent memory, then remembers on its own
+    #[default]
+    Inherit,
+    /// Starts empty and remembers on its own
+    Isolated,
+    /// Reads and writes the parent's memory
+    Shared,
+}
+
+impl std::fmt::Display for ThreadMemory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThreadMemory::Inherit => write!(f, "inherit"),
+            ThreadMemory::Isolated => write!(f, "isolated"),
+            ThreadMemory::Shared => write!(f, "shared"),
+        }
+    }
+}
+
+impl std::str::FromStr for ThreadMemory {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "inherit" => Ok(ThreadMemory::Inherit),
+            "isolated" => Ok(ThreadMemory::Isolated),
+            "shared" => Ok(ThreadMemory::Shared),
+            _ => bail!("Expected `inherit`, `isolated` or `shared`"),
+        }
+    }
+}
+
+/// Which memory a channel reads and writes
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MemoryScope {
+    /// The channel's own memory
+    #[default]
+    Channel,
+    /// One memory shared by every channel in the guild with this scope
+    Guild,
+    /// One memory shared by every channel in the guild linked to the named group
+    Group(String),
+}
+
+impl std::fmt::Display for MemoryScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MemoryScope::Channel => write!(f, "channel"),
+            MemoryScope::Guild => write!(f, "guild"),
+            MemoryScope::Group(group) => write!(f, "group:{group}"),
+        }
+    }
+}
+
+impl std::str::FromStr for MemoryScope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(group) = s.strip_prefix("group:") {
+            if !is_valid_persona_name(group) {
+                bail!("Group names may only contain letters, numbers, `-` and `_`");
+            }
+            return Ok(MemoryScope::Group(group.to_string()));
+        }
+
+        match s.to_lowercase().as_str() {
+            "channel" => Ok(MemoryScope::Channel),
+            "guild" => Ok(MemoryScope::Guild),
+            _ => bail!("Expected `channel`, `guild` or `group:<name>`"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingName {
+    Temperature,
+    HistoryLength,
+    MaxTokens,
+    Model,
+    Persona,
+    Candidates,
+    Passive,
+    AutoReply,
+    ChimeInChance,
+    ChimeInCooldown,
+    ChimeInKeywords,
+    QuietHours,
+    ReactChance,
+    RetentionCount,
+    RetentionDays,
+    InactiveDays,
+    ThreadMemory,
+    MemoryScope,
+}
+
+impl SettingName {
+    pub const ALL: [SettingName; 18] = [
+        SettingName::Temperature,
+        SettingName::HistoryLength,
+        SettingName::MaxTokens,
+        SettingName::Model,
+        SettingName::Persona,
+        SettingName::Candidates,
+        SettingName::Passive,
+        SettingName::AutoReply,
+        SettingName::ChimeInChance,
+        SettingName::ChimeInCooldown,
+        SettingName::ChimeInKeywords,
+        SettingName::QuietHours,
+        SettingName::ReactChance,
+        SettingName::RetentionCount,
+        SettingName::RetentionDays,
+        SettingName::InactiveDays,
+        SettingName::ThreadMemory,
+        SettingName::MemoryScope,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            SettingName::Temperature => "temperature",
+            SettingName::HistoryLength => "history_length",
+            SettingName::MaxTokens => "max_tokens",
+            SettingName::Model => "model",
+            SettingName::Persona => "persona",
+            SettingName::Candidates => "candidates",
+            SettingName::Passive => "passive",
+            SettingName::AutoReply => "auto_reply",
+            SettingName::ChimeInChance => "chime_in_chance",
+            SettingName::ChimeInCooldown => "chime_in_cooldown",
+            SettingName::ChimeInKeywords => "chime_in_keywords",
+            SettingName::QuietHours => "quiet_hours",
+            SettingName::ReactChance => "react_chance",
+            SettingName::RetentionCount => "retention_count",
+            SettingName::RetentionDays => "retention_days",
+            SettingName::InactiveDays => "inactive_days",
+            SettingName::ThreadMemory => "thread_memory",
+            SettingName::MemoryScope => "memory_scope",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|it| it.id() == id)
+    }
+}
+
+impl ChannelSettings {
+    pub fn get(&self, setting: SettingName) -> Option<String> {
+        match setting {
+            SettingName::Temperature => self.temperature.map(|it| it.to_string()),
+            SettingName::HistoryLength => self.history_length.map(|it| it.to_string()),
+            SettingName::MaxTokens => self.max_tokens.map(|it| it.to_string()),
+            SettingName::Model => self.model.clone(),
+            SettingName::Persona => self.persona.clone(),
+            SettingName::Candidates => self.candidates.map(|it| it.to_string()),
+            SettingName::Passive => self.passive.map(|it| it.to_string()),
+            SettingName::AutoReply => self.auto_reply.map(|it| it.to_string()),
+            SettingName::ChimeInChance => self.chime_in_chance.map(|it| it.to_string()),
+            SettingName::ChimeInCooldown => self.chime_in_cooldown.map(|it| it.to_string()),
+            SettingName::ChimeInKeywords => self.chime_in_keywords.clone(),
+            SettingName::QuietHours => self.quiet_hours.map(|it| it.to_string()),
+            SettingName::ReactChance => self.react_chance.map(|it| it.to_string()),
+            SettingName::RetentionCount => self.retention_count.map(|it| it.to_string()),
+            SettingName::RetentionDays => self.retention_days.map(|it| it.to_string()),
+            SettingName::InactiveDays => self.inactive_days.map(|it| it.to_string()),
+            SettingName::ThreadMemory => self.thread_memory.map(|it| it.to_string()),
+            SettingName::MemoryScope => self.memory_scope.as_ref().map(|it| it.to_string()),
+        }
+    }
+
+    pub fn set(&mut self, setting: SettingName, value: &str) -> anyhow::Result<()> {
+        match setting {
+            SettingName::Temperature => {
+                let temperature = value.parse::<f32>().context("Parse temperature")?;
+                if !(0.0..=2.0).contains(&temperature) {
+                    bail!("Temperature must be between 0 and 2");
+                }
+                self.temperature = Some(temperature);
+            }
+            SettingName::HistoryLength => {
+                let history_length = value.parse::<usize>().context("Parse history length")?;
+                if history_length > 200 {
+                    bail!("History length must be at most 200");
+                }
+                self.history_length = Some(history_length);
+            }
+            SettingName::MaxTokens => {
+                self.max_tokens = Some(value.parse().context("Parse max tokens")?);
+            }
+            SettingName::Model => self.model = Some(value.to_string()),
+            SettingName::Persona => {
+                if !is_valid_persona_name(value) {
+                    bail!("Persona names may only contain letters, numbers, `-` and `_`");
+                }
+                self.persona = Some(value.to_string());
+            }
+            SettingName::Candidates => {
+                let candidates = value.parse::<usize>().context("Parse candidates")?;
+                if !(1..=MAX_CANDIDATES).contains(&candidates) {
+                    bail!("Candidates must be between 1 and {MAX_CANDIDATES}");
+                }
+                self.candidates = Some(candidates);
+            }
+            SettingName::Passive => self.passive = Some(parse_bool(value)?),
+            SettingName::AutoReply => self.auto_reply = Some(parse_bool(value)?),
+            SettingName::ChimeInChance => {
+                let chance = value.parse::<f32>().context("Parse chime in chance")?;
+                if !(0.0..=1.0).contains(&chance) {
+                    bail!("Chime in chance must be between 0 and 1");
+                }
+                self.chime_in_chance = Some(chance);
+            }
+            SettingName::ChimeInCooldown => {
+                self.chime_in_cooldown = Some(value.parse().context("Parse chime in cooldown")?);
+            }
+            SettingName::ChimeInKeywords => self.chime_in_keywords = Some(value.to_string()),
+            SettingName::QuietHours => self.quiet_hours = Some(value.parse()?),
+            SettingName::ReactChance => {
+                let chance = value.parse::<f32>().context("Parse react chance")?;
+                if !(0.0..=1.0).contains(&chance) {
+                    bail!("React chance must be between 0 and 1");
+                }
+                self.react_chance = Some(chance);
+            }
+            SettingName::RetentionCount => {
+                self.retention_count = Some(value.parse().context("Parse retention count")?);
+            }
+            SettingName::RetentionDays => {
+                self.retention_days = Some(value.parse().context("Parse retention days")?);
+            }
+            SettingName::InactiveDays => {
+                self.inactive_days = Some(value.parse().context("Parse inactive days")?);
+            }
+            SettingName::ThreadMemory => self.thread_memory = Some(value.parse()?),
+            SettingName::MemoryScope => self.memory_scope = Some(value.parse()?),
+        }
+
+        Ok(())
+    }
+
+    pub fn reset(&mut self, setting: SettingName) {
+        match setting {
+            SettingName::Temperature => self.temperature = None,
+            SettingName::HistoryLength => self.history_length = None,
+            SettingName::MaxTokens => self.max_tokens = None,
+            SettingName::Model => self.model = None,
+            SettingName::Persona => self.persona = None,
+            SettingName::Candidates => self.candidates = None,
+            SettingName::Passive => self.passive = None,
+            SettingName::AutoReply => self.auto_reply = None,
+            SettingName::ChimeInChance => self.chime_in_chance = None,
+            SettingName::ChimeInCooldown => self.chime_in_cooldown = None,
+            SettingName::ChimeInKeywords => self.chime_in_keywords = None,
+            SettingName::QuietHours => self.quiet_hours = None,
+            SettingName::ReactChance => self.react_chance = None,
+            SettingName::RetentionCount => self.retention_count = None,
+            SettingName::RetentionDays => self.retention_days = None,
+            SettingName::InactiveDays => self.inactive_days = None,
+            SettingName::ThreadMemory => self.thread_memory = None,
+            SettingName::MemoryScope => self.memory_scope = None,
+        }
+    }
+
+    /// Fills in any unset values from `fallback`
+    pub fn or(self, fallback: &ChannelSettings) -> ChannelSettings {
+        ChannelSettings {
+            temperature: self.temperature.or(fallback.temperature),
+            history_length: self.history_length.or(fallback.history_length),
+            max_tokens: self.max_tokens.or(fallback.max_tokens),
+            model: self.model.or_else(|| fallback.model.clone()),
+            persona: self.persona.or_else(|| fallback.persona.clone()),
+            candidates: self.candidates.or(fallback.candidates),
+            passive: self.passive.or(fallback.passive),
+            auto_reply: self.auto_reply.or(fallback.auto_reply),
+            chime_in_chance: self.chime_in_chance.or(fallback.chime_in_chance),
+            chime_in_cooldown: self.chime_in_cooldown.or(fallback.chime_in_cooldown),
+            chime_in_keywords: self
+                .chime_in_keywords
+                .or_else(|| fallback.chime_in_keywords.clone()),
+            quiet_hours: self.quiet_hours.or(fallback.quiet_hours),
+            react_chance: self.react_chance.or(fallback.react_chance),
+            retention_count: self.retention_count.or(fallback.retention_count),
+            retention_days: self.retention_days.or(fallback.retention_days),
+            inactive_days: self.inactive_days.or(fallback.inactive_days),
+            thread_memory: self.thread_memory.or(fallback.thread_memory),
+            memory_scope: self.memory_scope.or_else(|| fallback.memory_scope.clone()),
+        }
+    }
+
+    /// The global fallbacks, read from env vars
+    pub fn from_env() -> anyhow::Result<ChannelSettings> {
+        fn parse_env<T: std::str::FromStr>(name: &str) -> anyhow::Result<Option<T>>
+        where
+            T::Err: std::error::Error + Send + Sync + 'static,
+        {
+            env::var(name)
+                .ok()
+                .map(|it| it.parse::<T>())
+                .transpose()
+                .with_context(|| format!("Parse `{name}`"))
+        }
+
+        Ok(ChannelSettings {
+            temperature: parse_env("LLM_TEMPERATURE")?,
+            history_length: Some(parse_env("LLM_HISTORY_LENGTH")?.unwrap_or(20)),
+            max_tokens: parse_env("LLM_MAX_TOKENS")?,
+            model: env::var("LLM_MODEL").ok(),
+            persona: None,
+            candidates: Some(parse_env("LLM_CANDIDATES")?.unwrap_or(1)),
+            passive: Some(false),
+            auto_reply: Some(parse_env("LLM_AUTO_REPLY")?.unwrap_or(false)),
+            chime_in_chance: Some(parse_env("LLM_CHIME_IN_CHANCE")?.unwrap_or(0.0)),
+            chime_in_cooldown: Some(parse_env("LLM_CHIME_IN_COOLDOWN")?.unwrap_or(300)),
+            chime_in_keywords: None,
+            quiet_hours: None,
+            react_chance: Some(parse_env("LLM_REACT_CHANCE")?.unwrap_or(0.0)),
+            // `LLM_PASSIVE_RETENTION` predates retention applying to every message
+            retention_count: Some(
+                parse_env("LLM_RETENTION_COUNT")?
+                    .or(parse_env("LLM_PASSIVE_RETENTION")?)
+                    .unwrap_or(500),
+            ),
+            retention_days: Some(parse_env("LLM_RETENTION_DAYS")?.unwrap_or(0)),
+            inactive_days: Some(parse_env("LLM_INACTIVE_DAYS")?.unwrap_or(0)),
+            thread_memory: Some(
+                env::var("LLM_THREAD_MEMORY")
+                    .ok()
+                    .map(|it| it.parse::<ThreadMemory>())
+                    .transpose()
+                    .context("Parse `LLM_THREAD_MEMORY`")?
+                    .unwrap_or_default(),
+            ),
+            memory_scope: Some(MemoryScope::Channel),
+        })
+    }
+}
+
+/// Fully resolved settings for one generation
+#[derive(Debug, Clone)]
+pub struct GenerationSettings {
+    pub temperature: Option<f32>,
+    pub history_length: usize,
+    pub max_tokens: Option<u32>,
+    pub model: String,
+    /// `None` uses the prompt in `LLM_PROMPT_FILE`
+    pub persona: Option<String>,
+    pub candidates: usize,
+    /// The guild's own words, merged with `LLM_WORDS_FILE` when rendering prompts
+    pub words: Vec<String>,
+    /// The guild's facts about its members, the ones about participants go in the prompt
+    pub facts: UserFacts,
+    pub retention: RetentionPolicy,
+}
+
+impl GenerationSettings {
+    pub fn resolve(channel: ChannelSettings, guild: &GuildSettings) -> anyhow::Result<Self> {
+        let channel = channel
+            .or(&guild.channel_defaults)
+            .or(&ChannelSettings::from_env()?);
+        let retention = RetentionPolicy::from_settings(&channel);
+        let ChannelSettings {
+            temperature,
+            history_length,
+            max_tokens,
+            model,
+            persona,
+            candidates,
+            ..
+        } = channel;
+
+        Ok(GenerationSettings {
+            temperature,
+            history_length: history_length.unwrap_or(20),
+            max_tokens,
+            model: model.context("Expected a llm model in env")?,
+            persona,
+            candidates: candidates.unwrap_or(1),
+            words: Vec::new(),
+            facts: UserFacts::default(),
+            retention,
+        })
+    }
+
+    /// The number of candidates to actually generate, capped so the worst case completion
+    /// tokens stay within `LLM_CANDIDATE_TOKEN_BUDGET`
+    pub fn candidate_count(&self) -> usize {
+        let budget = env::var("LLM_CANDIDATE_TOKEN_BUDGET")
+            .ok()
+            .and_then(|it| it.parse::<u32>().ok())
+            .unwrap_or(2048);
+        let per_candidate = self.max_tokens.unwrap_or(256).max(1);
+        let affordable = (budget / per_candidate).max(1) as usize;
+
+        self.candidates.clamp(1, MAX_CANDIDATES).min(affordable)
+    }
+
+    /// Path to the system prompt, personas live in `LLM_PERSONA_DIR` as `<name>.txt`
+    pub fn prompt_file(&self) -> String {
+        match &self.persona {
+            Some(persona) => persona_path(persona),
+            None => env::var("LLM_PROMPT_FILE").unwrap_or_else(|_| "./prompt.txt".to_string()),
+        }
+    }
+}
+
+fn parse_bool(value: &str) -> anyhow::Result<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "on" | "yes" | "1" => Ok(true),
+        "false" | "off" | "no" | "0" => Ok(false),
+        _ => bail!("Expected `true` or `false`"),
+    }
+}
+
+/// Where the prompt for a persona lives, in `LLM_PERSONA_DIR`
+pub fn persona_path(name: &str) -> String {
+    let dir = env::var("LLM_PERSONA_DIR").unwrap_or_else(|_| "./personas".to_string());
+    format!("{dir}/{name}.txt")
+}
+
+pub fn is_valid_persona_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|it| it.is_ascii_alphanumeric() || it == '-' || it == '_')
+}
@@ -0,0 +1,712 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::{fmt, io};
+
+use anyhow::Context as _;
+use llm::chat::Usage;
+use redb::{
+    CommitError, Database, DatabaseError, ReadableDatabase, ReadableTable, ReadableTableMetadata,
+    StorageError, TableDefinition, TableError, TransactionError,
+};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value, json};
+use serenity::all::{ChannelId, GuildId, User, UserId};
+use tracing::{error, info};
+
+use crate::ai::MakaiMessage;
+use crate::context::SharedMemoryId;
+use crate::context::serde::{MakaiContextGuildSerde, MakaiContextSerde};
+use crate::message_log::MessageKey;
+use crate::personas::PersonaConsent;
+use crate::schema::{self, SCHEMA_VERSION};
+use crate::settings::ChannelSettings;
+
+/// Messages keyed by channel, then timestamp in nanoseconds, then sequence number
+const MESSAGES: TableDefinition<(u64, i64, u64), &[u8]> = TableDefinition::new("messages_v2");
+/// Messages keyed by channel, then timestamp in nanoseconds, before schema version 2
+const MESSAGES_V1: TableDefinition<(u64, i64), &[u8]> = TableDefinition::new("messages");
+/// Messages in shared memories keyed by guild, then group (empty for the whole guild), then
+/// timestamp in nanoseconds, then sequence number
+const SHARED_MESSAGES: TableDefinition<(u64, &str, i64, u64), &[u8]> =
+    TableDefinition::new("shared_messages");
+/// Channel settings keyed by channel
+const CHANNELS: TableDefinition<u64, &[u8]> = TableDefinition::new("channels");
+/// The guild each channel was last seen in, keyed by channel
+const CHANNEL_GUILDS: TableDefinition<u64, u64> = TableDefinition::new("channel_guilds");
+/// The channel each thread was started from, keyed by thread
+const CHANNEL_PARENTS: TableDefinition<u64, u64> = TableDefinition::new("channel_parents");
+/// Guild settings and word lists keyed by guild
+const GUILDS: TableDefinition<u64, &[u8]> = TableDefinition::new("guilds");
+/// Token usage keyed by channel, as prompt and completion tokens
+const USAGE: TableDefinition<u64, (u64, u64)> = TableDefinition::new("usage");
+/// Token usage of shared memories keyed by guild and group, like `USAGE`
+const SHARED_USAGE: TableDefinition<(u64, &str), (u64, u64)> = TableDefinition::new("shared_usage");
+/// Global state that isn't tied to a channel or guild
+const META: TableDefinition<&str, &[u8]> = TableDefinition::new("meta");
+
+const META_OPTED_OUT_USERS: &str = "opted_out_users";
+const META_PERSONA_CONSENT: &str = "persona_consent";
+const META_BOT_USER: &str = "bot_user";
+const META_SCHEMA_VERSION: &str = "schema_version";
+
+/// Databases from before the schema version was stored were written in this layout
+const DB_SCHEMA_VERSION: u32 = 1;
+/// The most queued writes committed in one transaction
+const MAX_WRITE_BATCH: usize = 1024;
+
+/// Persists the bot's state in an embedded database, written to as it changes. Values are
+/// stored as json, like the old state file, so serde defaults keep working for new fields
+pub struct Store {
+    db: Arc<Database>,
+    writer: Option<Writer>,
+}
+
+type WriteOp = Box<dyn FnOnce(&redb::WriteTransaction) -> anyhow::Result<()> + Send>;
+
+enum Queued {
+    Write {
+        what: &'static str,
+        write: WriteOp,
+    },
+    /// Answered once everything queued before it is committed
+    Flush(mpsc::Sender<()>),
+}
+
+/// Commits queued changes on its own thread, so callers never wait on the disk while they
+/// hold a lock on the state they're writing
+struct Writer {
+    queue: mpsc::Sender<Queued>,
+    thread: thread::JoinHandle<()>,
+}
+
+impl Writer {
+    fn spawn(db: Arc<Database>) -> Self {
+        let (queue, queued) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("store-writer".to_string())
+            .spawn(move || Self::run(&db, queued))
+            .expect("Spawn store writer");
+
+        Self { queue, thread }
+    }
+
+    /// Commits everything queued since the last commit in one transaction, so a burst of
+    /// messages costs a single sync
+    fn run(db: &Database, queued: mpsc::Receiver<Queued>) {
+        while let Ok(first) = queued.recv() {
+            let batch = [first]
+                .into_iter()
+                .chain(queued.try_iter().take(MAX_WRITE_BATCH - 1));
+
+            let mut flushes = Vec::new();
+            let res = (|| {
+                let txn = db.begin_write()?;
+                for queued in batch {
+                    match queued {
+                        Queued::Write { what, write } => {
+                            if let Err(err) = write(&txn) {
+                                error!("Cannot write {what} to the database: {err:?}");
+                            }
+                        }
+                        Queued::Flush(done) => flushes.push(done),
+                    }
+                }
+                txn.commit()?;
+
+                anyhow::Ok(())
+            })();
+
+            if let Err(err) = res {
+                error!("Cannot commit to the database: {err:?}");
+            }
+            for done in flushes {
+                let _ = done.send(());
+            }
+        }
+    }
+}
+
+impl Store {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let db = Database::create(path).context("Open database")?;
+
+        // Make sure every table exists, so reads never have to handle missing ones
+        let txn = db.begin_write()?;
+        txn.open_table(MESSAGES)?;
+        txn.open_table(SHARED_MESSAGES)?;
+        txn.open_table(CHANNELS)?;
+        txn.open_table(CHANNEL_GUILDS)?;
+        txn.open_table(CHANNEL_PARENTS)?;
+        txn.open_table(GUILDS)?;
+        txn.open_table(USAGE)?;
+        txn.open_table(SHARED_USAGE)?;
+        txn.open_table(META)?;
+        txn.commit().context("Create tables")?;
+
+        let db = Arc::new(db);
+        Ok(Self {
+            writer: Some(Writer::spawn(db.clone())),
+            db,
+        })
+    }
+
+    /// Shrinks the database file after lots of data was removed
+    pub fn compact(&mut self) -> anyhow::Result<()> {
+        self.stop_writer();
+        Arc::get_mut(&mut self.db)
+            .context("Database is still being written")?
+            .compact()?;
+        self.writer = Some(Writer::spawn(self.db.clone()));
+
+        Ok(())
+    }
+
+    /// Blocks until every change queued so far is committed
+    pub fn flush(&self) {
+        let (done, flushed) = mpsc::channel();
+        if let Some(writer) = &self.writer
+            && writer.queue.send(Queued::Flush(done)).is_ok()
+        {
+            let _ = flushed.recv();
+        }
+    }
+
+    /// Commits whatever is still queued and stops the writer
+    fn stop_writer(&mut self) {
+        if let Some(Writer { queue, thread }) = self.writer.take() {
+            drop(queue);
+            if thread.join().is_err() {
+                error!("The store writer panicked");
+            }
+        }
+    }
+
+    /// Whether nothing has been stored yet, eg. before migrating the old state file
+    pub fn is_empty(&self) -> anyhow::Result<bool> {
+        let txn = self.db.begin_read()?;
+
+        Ok(txn.open_table(MESSAGES)?.is_empty()?
+            && txn.open_table(SHARED_MESSAGES)?.is_empty()?
+            && txn.open_table(CHANNELS)?.is_empty()?
+            && txn.open_table(GUILDS)?.is_empty()?
+            && txn.open_table(META)?.is_empty()?)
+    }
+
+    /// Reads the whole state back, upgrading it if it was written by an older version
+    pub fn load(&self) -> anyhow::Result<MakaiContextSerde> {
+        let version = self.schema_version()?;
+        let txn = self.db.begin_read()?;
+
+        // Messages were keyed by timestamp before version 2, and are a list since
+        let no_messages = if version < 2 { json!({}) } else { json!([]) };
+
+        let mut channels = Map::new();
+        let mut shared = Map::new();
+        for entry in txn.open_table(CHANNELS)?.iter()? {
+            let (channel, settings) = entry?;
+            channel_entry(&mut channels, channel.value(), &no_messages)["settings"] =
+                decode(settings.value()).context("Decode channel settings")?;
+        }
+        for entry in txn.open_table(CHANNEL_GUILDS)?.iter()? {
+            let (channel, guild) = entry?;
+            channel_entry(&mut channels, channel.value(), &no_messages)["guild"] =
+                guild.value().into();
+        }
+        for entry in txn.open_table(CHANNEL_PARENTS)?.iter()? {
+            let (channel, parent) = entry?;
+            channel_entry(&mut channels, channel.value(), &no_messages)["parent"] =
+                parent.value().into();
+        }
+        if version < 2 {
+            let table = match txn.open_table(MESSAGES_V1) {
+                Ok(table) => Some(table),
+                Err(TableError::TableDoesNotExist(_)) => None,
+                Err(err) => return Err(err.into()),
+            };
+            let entries = table.as_ref().map(|it| it.iter()).transpose()?;
+            for entry in entries.into_iter().flatten() {
+                let (key, message) = entry?;
+                let (channel, _) = key.value();
+                let message: Value = decode(message.value()).context("Decode message")?;
+                let timestamp = message["timestamp"]
+                    .as_str()
+                    .context("Message has no timestamp")?
+                    .to_string();
+
+                channel_entry(&mut channels, channel, &no_messages)["messages"]
+                    .as_object_mut()
+                    .context("Messages are not an object")?
+                    .insert(timestamp, message);
+            }
+        } else {
+            for entry in txn.open_table(MESSAGES)?.iter()? {
+                let (key, message) = entry?;
+                let (channel, _, seq) = key.value();
+                let mut message: Value = decode(message.value()).context("Decode message")?;
+                message["seq"] = seq.into();
+
+                channel_entry(&mut channels, channel, &no_messages)["messages"]
+                    .as_array_mut()
+                    .context("Messages are not a list")?
+                    .push(message);
+            }
+            // Shared memories were added after messages became a list
+            for entry in txn.open_table(SHARED_MESSAGES)?.iter()? {
+                let (key, message) = entry?;
+                let (guild, group, _, seq) = key.value();
+                let mut message: Value = decode(message.value()).context("Decode message")?;
+                message["seq"] = seq.into();
+
+                shared.entry(format!("{guild}:{group}")).or_insert_with(|| {
+                    json!({
+                        "guild": guild,
+                        "group": Some(group).filter(|it| !it.is_empty()),
+                        "messages": [],
+                    })
+                })["messages"]
+                    .as_array_mut()
+                    .context("Messages are not a list")?
+                    .push(message);
+            }
+        }
+
+        let mut guilds = Map::new();
+        for entry in txn.open_table(GUILDS)?.iter()? {
+            let (guild, value) = entry?;
+            guilds.insert(
+                guild.value().to_string(),
+                decode(value.value()).context("Decode guild")?,
+            );
+        }
+
+        let mut state = json!({
+            "channels": channels,
+            "guilds": guilds,
+            "shared": shared.into_values().collect::<Vec<_>>(),
+            "bot_user": null,
+        });
+        let meta = txn.open_table(META)?;
+        for key in [META_OPTED_OUT_USERS, META_PERSONA_CONSENT, META_BOT_USER] {
+            if let Some(value) = meta.get(key)? {
+                state[key] = decode(value.value()).with_context(|| format!("Decode {key}"))?;
+            }
+        }
+
+        schema::migrate_state(state, version)
+    }
+
+    /// Rewrites everything stored by an older version in the current layout
+    pub fn migrate(&self) -> anyhow::Result<()> {
+        let version = self.schema_version()?;
+        if version >= SCHEMA_VERSION {
+            return Ok(());
+        }
+
+        info!("Migrating the database from schema version {version} to {SCHEMA_VERSION}");
+        let state = self.load()?;
+
+        let txn = self.db.begin_write()?;
+        txn.open_table(MESSAGES)?.retain(|_, _| false)?;
+        txn.delete_table(MESSAGES_V1)?;
+        txn.open_table(SHARED_MESSAGES)?.retain(|_, _| false)?;
+        txn.open_table(CHANNELS)?.retain(|_, _| false)?;
+        txn.open_table(CHANNEL_GUILDS)?.retain(|_, _| false)?;
+        txn.open_table(CHANNEL_PARENTS)?.retain(|_, _| false)?;
+        txn.open_table(GUILDS)?.retain(|_, _| false)?;
+        Self::write_state(&txn, &state)?;
+        txn.commit().context("Commit migration")?;
+
+        Ok(())
+    }
+
+    fn schema_version(&self) -> anyhow::Result<u32> {
+        match self
+            .db
+            .begin_read()?
+            .open_table(META)?
+            .get(META_SCHEMA_VERSION)?
+        {
+            Some(value) => decode(value.value()).context("Decode schema version"),
+            None => Ok(DB_SCHEMA_VERSION),
+        }
+    }
+
+    /// Writes a whole state in one transaction, used to migrate the old json state file
+    pub fn import(&self, state: &MakaiContextSerde) -> anyhow::Result<()> {
+        let txn = self.db.begin_write()?;
+        Self::write_state(&txn, state)?;
+        txn.commit().context("Commit import")?;
+
+        Ok(())
+    }
+
+    fn write_state(txn: &redb::WriteTransaction, state: &MakaiContextSerde) -> anyhow::Result<()> {
+        let mut messages = txn.open_table(MESSAGES)?;
+        let mut channels = txn.open_table(CHANNELS)?;
+        let mut channel_guilds = txn.open_table(CHANNEL_GUILDS)?;
+        let mut channel_parents = txn.open_table(CHANNEL_PARENTS)?;
+        for (channel, ctx) in &state.channels {
+            channels.insert(channel.get(), encode(&ctx.settings)?.as_slice())?;
+            if let Some(guild) = ctx.guild {
+                channel_guilds.insert(channel.get(), guild.get())?;
+            }
+            if let Some(parent) = ctx.parent {
+                channel_parents.insert(channel.get(), parent.get())?;
+            }
+            for (key, message) in ctx.messages.iter() {
+                messages.insert(message_key(*channel, key), encode(message)?.as_slice())?;
+            }
+        }
+
+        let mut shared_messages = txn.open_table(SHARED_MESSAGES)?;
+        for memory in &state.shared {
+            let id = SharedMemoryId {
+                guild: memory.guild,
+                group: memory.group.clone(),
+            };
+            for (key, message) in memory.messages.iter() {
+                shared_messages
+                    .insert(shared_message_key(&id, key), encode(message)?.as_slice())?;
+            }
+        }
+
+        let mut guilds = txn.open_table(GUILDS)?;
+        for (guild, ctx) in &state.guilds {
+            guilds.insert(guild.get(), encode(ctx)?.as_slice())?;
+        }
+
+        let mut meta = txn.open_table(META)?;
+        meta.insert(
+            META_OPTED_OUT_USERS,
+            encode(&state.opted_out_users)?.as_slice(),
+        )?;
+        meta.insert(
+            META_PERSONA_CONSENT,
+            encode(&state.persona_consent)?.as_slice(),
+        )?;
+        meta.insert(META_BOT_USER, encode(&state.bot_user)?.as_slice())?;
+        meta.insert(META_SCHEMA_VERSION, encode(&SCHEMA_VERSION)?.as_slice())?;
+
+        Ok(())
+    }
+
+    pub fn put_opted_out_users(&self, users: &HashSet<UserId>) {
+        self.put_meta(META_OPTED_OUT_USERS, users);
+    }
+
+    pub fn put_persona_consent(&self, consent: &HashMap<UserId, PersonaConsent>) {
+        self.put_meta(META_PERSONA_CONSENT, consent);
+    }
+
+    pub fn put_bot_user(&self, user: &Option<User>) {
+        self.put_meta(META_BOT_USER, user);
+    }
+
+    fn put_meta(&self, key: &'static str, value: &impl Serialize) {
+        let value = match encode(value) {
+            Ok(value) => value,
+            Err(err) => {
+                error!("Cannot encode {key}: {err:?}");
+                return;
+            }
+        };
+
+        self.write("meta", move |txn| {
+            txn.open_table(META)?.insert(key, value.as_slice())?;
+            Ok(())
+        });
+    }
+
+    /// Queues a write for the next commit, logging failures since callers can't do anything
+    /// about them
+    fn write(
+        &self,
+        what: &'static str,
+        write: impl FnOnce(&redb::WriteTransaction) -> anyhow::Result<()> + Send + 'static,
+    ) {
+        let write = Box::new(write);
+        let queued = self
+            .writer
+            .as_ref()
+            .is_some_and(|it| it.queue.send(Queued::Write { what, write }).is_ok());
+
+        if !queued {
+            error!("Cannot write {what} to the database, the writer is stopped");
+        }
+    }
+}
+
+impl Drop for Store {
+    fn drop(&mut self) {
+        self.stop_writer();
+    }
+}
+
+/// Which memory a `ChannelStore` writes to
+#[derive(Debug, Clone)]
+pub enum MemoryId {
+    Channel(ChannelId),
+    /// Shared memories only hold messages and usage, settings stay with the channels
+    Shared(SharedMemoryId),
+}
+
+/// Writes one channel's, or shared memory's, changes through to the store
+#[derive(Clone)]
+pub struct ChannelStore {
+    pub store: Arc<Store>,
+    pub memory: MemoryId,
+}
+
+impl ChannelStore {
+    pub fn put_messages<'a>(
+        &self,
+        messages: impl IntoIterator<Item = (&'a MessageKey, &'a MakaiMessage)>,
+    ) {
+        let messages = messages
+            .into_iter()
+            .map(|(key, message)| (*key, message.clone()))
+            .collect::<Vec<_>>();
+        let memory = self.memory.clone();
+
+        self.store.write("messages", move |txn| {
+            match &memory {
+                MemoryId::Channel(channel) => {
+                    let mut table = txn.open_table(MESSAGES)?;
+                    for (key, message) in messages {
+                        table.insert(message_key(*channel, &key), encode(&message)?.as_slice())?;
+                    }
+                }
+                MemoryId::Shared(shared) => {
+                    let mut table = txn.open_table(SHARED_MESSAGES)?;
+                    for (key, message) in messages {
+                        table.insert(
+                            shared_message_key(shared, &key),
+                            encode(&message)?.as_slice(),
+                        )?;
+                    }
+                }
+            }
+            Ok(())
+        });
+    }
+
+    pub fn remove_messages(&self, keys: impl IntoIterator<Item = MessageKey>) {
+        let keys = keys.into_iter().collect::<Vec<_>>();
+        let memory = self.memory.clone();
+
+        self.store.write("messages", move |txn| {
+            match &memory {
+                MemoryId::Channel(channel) => {
+                    let mut table = txn.open_table(MESSAGES)?;
+                    for key in keys {
+                        table.remove(message_key(*channel, &key))?;
+                    }
+                }
+                MemoryId::Shared(shared) => {
+                    let mut table = txn.open_table(SHARED_MESSAGES)?;
+                    for key in keys {
+                        table.remove(shared_message_key(shared, &key))?;
+                    }
+                }
+            }
+            Ok(())
+        });
+    }
+
+    pub fn clear(&self) {
+        let memory = self.memory.clone();
+
+        self.store.write("messages", move |txn| {
+            match &memory {
+                MemoryId::Channel(channel) => {
+                    let channel = channel.get();
+                    txn.open_table(MESSAGES)?.retain_in(
+                        (channel, i64::MIN, u64::MIN)..=(channel, i64::MAX, u64::MAX),
+                        |_, _| false,
+                    )?;
+                }
+                MemoryId::Shared(shared) => {
+                    let (guild, group) = shared_key(shared);
+                    txn.open_table(SHARED_MESSAGES)?.retain_in(
+                        (guild, group, i64::MIN, u64::MIN)..=(guild, group, i64::MAX, u64::MAX),
+                        |_, _| false,
+                    )?;
+                }
+            }
+            Ok(())
+        });
+    }
+
+    pub fn put_settings(&self, settings: &ChannelSettings) {
+        let MemoryId::Channel(channel) = self.memory else {
+            return;
+        };
+        let settings = settings.clone();
+        self.store.write("channel settings", move |txn| {
+            txn.open_table(CHANNELS)?
+                .insert(channel.get(), encode(&settings)?.as_slice())?;
+            Ok(())
+        });
+    }
+
+    pub fn put_guild(&self, guild: GuildId) {
+        let MemoryId::Channel(channel) = self.memory else {
+            return;
+        };
+        self.store.write("channel guild", move |txn| {
+            txn.open_table(CHANNEL_GUILDS)?
+                .insert(channel.get(), guild.get())?;
+            Ok(())
+        });
+    }
+
+    pub fn put_parent(&self, parent: ChannelId) {
+        let MemoryId::Channel(channel) = self.memory else {
+            return;
+        };
+        self.store.write("channel parent", move |txn| {
+            txn.open_table(CHANNEL_PARENTS)?
+                .insert(channel.get(), parent.get())?;
+            Ok(())
+        });
+    }
+
+    pub fn add_usage(&self, usage: &Usage) {
+        let (prompt_tokens, completion_tokens) = (
+            u64::from(usage.prompt_tokens),
+            u64::from(usage.completion_tokens),
+        );
+        let add = move |(prompt, completion): (u64, u64)| {
+            (prompt + prompt_tokens, completion + completion_tokens)
+        };
+        let memory = self.memory.clone();
+
+        self.store.write("usage", move |txn| {
+            match &memory {
+                MemoryId::Channel(channel) => {
+                    let mut table = txn.open_table(USAGE)?;
+                    let total = table
+                        .get(channel.get())?
+                        .map(|it| it.value())
+                        .unwrap_or_default();
+                    table.insert(channel.get(), add(total))?;
+                }
+                MemoryId::Shared(shared) => {
+                    let mut table = txn.open_table(SHARED_USAGE)?;
+                    let total = table
+                        .get(shared_key(shared))?
+                        .map(|it| it.value())
+                        .unwrap_or_default();
+                    table.insert(shared_key(shared), add(total))?;
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Writes one guild's changes through to the store
+#[derive(Clone)]
+pub struct GuildStore {
+    pub store: Arc<Store>,
+    pub guild: GuildId,
+}
+
+impl GuildStore {
+    pub fn put(&self, guild: MakaiContextGuildSerde) {
+        let id = self.guild;
+        self.store.write("guild", move |txn| {
+            txn.open_table(GUILDS)?
+                .insert(id.get(), encode(&guild)?.as_slice())?;
+            Ok(())
+        });
+    }
+}
+
+fn message_key(channel: ChannelId, key: &MessageKey) -> (u64, i64, u64) {
+    (
+        channel.get(),
+        key.timestamp.timestamp_nanos_opt().unwrap_or_default(),
+        key.seq,
+    )
+}
+
+fn shared_key(shared: &SharedMemoryId) -> (u64, &str) {
+    (
+        shared.guild.get(),
+        shared.group.as_deref().unwrap_or_default(),
+    )
+}
+
+fn shared_message_key<'a>(
+    shared: &'a SharedMemoryId,
+    key: &MessageKey,
+) -> (u64, &'a str, i64, u64) {
+    let (guild, group) = shared_key(shared);
+
+    (
+        guild,
+        group,
+        key.timestamp.timestamp_nanos_opt().unwrap_or_default(),
+        key.seq,
+    )
+}
+
+fn channel_entry<'a>(
+    channels: &'a mut Map<String, Value>,
+    channel: u64,
+    no_messages: &Value,
+) -> &'a mut Value {
+    channels
+        .entry(channel.to_string())
+        .or_insert_with(|| json!({ "messages": no_messages }))
+}
+
+fn encode(value: &impl Serialize) -> anyhow::Result<Vec<u8>> {
+    serde_json::to_vec(value).context("Encode value")
+}
+
+fn decode<T: DeserializeOwned>(value: &[u8]) -> anyhow::Result<T> {
+    serde_json::from_slice(value).map_err(|err| Undecodable(err).into())
+}
+
+/// A stored value that doesn't parse anymore
+#[derive(Debug)]
+struct Undecodable(serde_json::Error);
+
+impl fmt::Display for Undecodable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Decode value")
+    }
+}
+
+impl std::error::Error for Undecodable {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+/// Whether `err` means the database file itself is damaged, as opposed to eg. I/O errors,
+/// a database from a newer version or a failed migration, which a backup wouldn't fix
+pub fn is_corrupted(err: &anyhow::Error) -> bool {
+    // redb reports a file that isn't a database at all as invalid data
+    fn corrupted(err: &StorageError) -> bool {
+        match err {
+            StorageError::Corrupted(_) => true,
+            StorageError::Io(err) => err.kind() == io::ErrorKind::InvalidData,
+            _ => false,
+        }
+    }
+
+    err.chain().any(|err| {
+        err.is::<Undecodable>()
+            || err.downcast_ref::<StorageError>().is_some_and(corrupted)
+            || matches!(err.downcast_ref(), Some(DatabaseError::Storage(err)) if corrupted(err))
+            || matches!(err.downcast_ref(), Some(TransactionError::Storage(err)) if corrupted(err))
+            || matches!(err.downcast_ref(), Some(TableError::Storage(err)) if corrupted(err))
+            || matches!(err.downcast_ref(), Some(CommitError::Storage(err)) if corrupted(err))
+    })
+}
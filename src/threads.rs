@@ -0,0 +1,88 @@
+use serenity::all::{
+    ChannelId, ChannelType, CommandInteraction, Context, GuildChannel, GuildId, Message,
+};
+use tracing::debug;
+
+use crate::ai::MakaiMessage;
+use crate::context::MakaiContext;
+use crate::settings::ThreadMemory;
+
+/// Records a new thread's parent, and copies in the parent's recent memory if the thread
+/// inherits it, tagged so it stays apart from what the thread remembers itself
+pub async fn handle_thread_create(bot_ctx: &MakaiContext, thread: &GuildChannel) {
+    let is_thread = matches!(
+        thread.kind,
+        ChannelType::PublicThread | ChannelType::PrivateThread | ChannelType::NewsThread
+    );
+    let Some(parent) = thread.parent_id.filter(|_| is_thread) else {
+        return;
+    };
+
+    let ctx = bot_ctx.channel(&thread.id).await;
+    ctx.set_parent(parent).await;
+
+    // Also fired when makai is added to an existing thread, which already has its own memory
+    if bot_ctx.thread_memory(&thread.id).await != ThreadMemory::Inherit || !ctx.is_empty().await {
+        return;
+    }
+
+    let history_length = bot_ctx
+        .generation_settings(Some(thread.guild_id), &parent)
+        .await
+        .map(|it| it.history_length)
+        .unwrap_or(20);
+    let snapshot = bot_ctx
+        .memory(Some(thread.guild_id), &parent)
+        .await
+        .recent_messages(history_length)
+        .await;
+
+    debug!(
+        "Thread {} inherited {} messages from {parent}",
+        thread.id,
+        snapshot.len()
+    );
+    ctx.add_messages(snapshot.into_iter().map(|it| MakaiMessage {
+        inherited: true,
+        ..it
+    }))
+    .await;
+}
+
+/// Records the parent of a thread makai wasn't around to see created, from the cache
+pub async fn track_message(bot_ctx: &MakaiContext, discord_ctx: &Context, message: &Message) {
+    let Some(guild_id) = message.guild_id else {
+        return;
+    };
+
+    let ctx = bot_ctx.channel(&message.channel_id).await;
+    if ctx.parent().await.is_some() {
+        return;
+    }
+
+    if let Some(parent) = cached_parent(discord_ctx, guild_id, message.channel_id) {
+        ctx.set_parent(parent).await;
+    }
+}
+
+/// Records the parent of a thread a command was used in
+pub async fn track_command(bot_ctx: &MakaiContext, cmd: &CommandInteraction) {
+    let Some(channel) = &cmd.channel else {
+        return;
+    };
+    let is_thread = channel.thread_metadata.is_some();
+
+    if let Some(parent) = channel.parent_id.filter(|_| is_thread) {
+        bot_ctx.channel(&channel.id).await.set_parent(parent).await;
+    }
+}
+
+fn cached_parent(discord_ctx: &Context, guild_id: GuildId, thread: ChannelId) -> Option<ChannelId> {
+    discord_ctx
+        .cache
+        .guild(guild_id)?
+        .threads
+        .iter()
+        .find(|it| it.id == thread)
+        .and_then(|it| it.parent_id)
+}
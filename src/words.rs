@@ -0,0 +1,100 @@
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
+/// Longer entries are sentences, not inside jokes
+pub const MAX_WORD_LEN: usize = 100;
+/// Every word ends up in the system prompt, so a guild can't add too many
+pub const MAX_GUILD_WORDS: usize = 500;
+
+/// A guild's own makain words, merged with `LLM_WORDS_FILE` when rendering prompts
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WordList {
+    words: Vec<String>,
+    /// Suggestions waiting for an admin to approve them
+    pending: Vec<String>,
+}
+
+impl WordList {
+    pub fn words(&self) -> &[String] {
+        &self.words
+    }
+
+    pub fn pending(&self) -> &[String] {
+        &self.pending
+    }
+
+    /// Adds a word, or suggests it if `pending`. Returns false if it's already listed
+    pub fn add(&mut self, word: &str, pending: bool) -> anyhow::Result<bool> {
+        let word = normalize(word)?;
+        if self.contains(&word) {
+            return Ok(false);
+        }
+        if self.words.len() + self.pending.len() >= MAX_GUILD_WORDS {
+            bail!("This server already has {MAX_GUILD_WORDS} words");
+        }
+
+        if pending {
+            self.pending.push(word);
+        } else {
+            self.words.push(word);
+        }
+
+        Ok(true)
+    }
+
+    /// Removes a word or suggestion, returns whether it was listed
+    pub fn remove(&mut self, word: &str) -> bool {
+        let before = self.words.len() + self.pending.len();
+        self.words.retain(|it| !same_word(it, word));
+        self.pending.retain(|it| !same_word(it, word));
+
+        before != self.words.len() + self.pending.len()
+    }
+
+    /// Moves a suggestion into the word list, returns whether it was pending
+    pub fn approve(&mut self, word: &str) -> bool {
+        let Some(idx) = self.pending.iter().position(|it| same_word(it, word)) else {
+            return false;
+        };
+
+        let word = self.pending.remove(idx);
+        self.words.push(word);
+        true
+    }
+
+    /// Adds one word per line, returns how many were added and how many skipped
+    pub fn import(&mut self, text: &str) -> (usize, usize) {
+        text.lines()
+            .filter(|it| !it.trim().is_empty())
+            .fold((0, 0), |(added, skipped), it| match self.add(it, false) {
+                Ok(true) => (added + 1, skipped),
+                _ => (added, skipped + 1),
+            })
+    }
+
+    fn contains(&self, word: &str) -> bool {
+        self.words
+            .iter()
+            .chain(&self.pending)
+            .any(|it| same_word(it, word))
+    }
+}
+
+/// Trims and collapses whitespace, rejecting empty or overlong words
+fn normalize(word: &str) -> anyhow::Result<String> {
+    let word = word.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    if word.is_empty() {
+        bail!("Words can't be empty");
+    }
+    if word.chars().count() > MAX_WORD_LEN {
+        bail!("Words can be at most {MAX_WORD_LEN} characters long");
+    }
+
+    Ok(word)
+}
+
+pub fn same_word(a: &str, b: &str) -> bool {
+    a.trim().to_lowercase() == b.trim().to_lowercase()
+}